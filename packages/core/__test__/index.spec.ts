@@ -1,7 +1,7 @@
 import { test, expect, describe } from 'vitest'
 
 import { transformCode } from '../index'
-import { readdirSync, readFileSync } from 'node:fs'
+import { readFileSync } from 'node:fs'
 import { fileURLToPath } from 'url'
 import { dirname } from 'path'
 import path from 'node:path'
@@ -37,15 +37,13 @@ describe('Core tests', () => {
     const result = transformCode(flairPropertyObjectContent, 'index-3.tsx', {
       cssOutDir: path.resolve(__dirname, './.css'),
     })
-    const cssFiles = readdirSync(path.resolve(__dirname, './.css')).filter((f) => f.endsWith('.css'))
-    const cssContent = readFileSync(path.resolve(__dirname, './.css', cssFiles[0]), 'utf-8')
-
+    if (!result?.generatedCssName) {
+      throw new Error('transformCode did not write a css file')
+    }
+    // The css directory is shared with the other specs, so the file is looked up by name
+    const cssContent = readFileSync(path.resolve(__dirname, './.css', result.generatedCssName), 'utf-8')
 
     expect(cssContent).toMatchSnapshot()
-
-    if (!result) {
-      throw new Error('transformCode returned null or undefined')
-    }
     expect(result.code).toMatchSnapshot()
   })
 })
@@ -4,7 +4,7 @@ import { readFileSync } from 'node:fs'
 import path from 'node:path'
 import { dirname } from 'path'
 import { fileURLToPath } from 'url'
-import { transformCode } from '../index'
+import { transformCode, transformCodeAsync, transformMany } from '../index'
 
 const __filename = fileURLToPath(import.meta.url)
 const __dirname = dirname(__filename)
@@ -94,4 +94,335 @@ describe('Misc tests', () => {
     }
     expect(result.code).toMatchSnapshot()
   })
+
+  test('css errors point into the source file', () => {
+    const code = [
+      'export const Button = () => <button className="button">Click</button>',
+      '',
+      'Button.flair = `',
+      '  .button { color: red; }',
+      '  .a:::b { color: blue; }',
+      '`',
+      '',
+      'export const Card = () => <div className="card" />',
+      'Card.flair = `.card { color: blue; }`',
+    ].join('\n')
+    const result = transformCode(code, 'misc-8.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    const [error] = result.logs
+    expect(error?.file).toBe('misc-8.tsx')
+    expect(error?.level).toBe('error')
+    expect(error?.code).toBe('css-parse-error')
+    expect(error?.line).toBe(5)
+    expect(error?.frame).toContain('> 5 |   .a:::b { color: blue; }')
+  })
+
+  test('logs are returned for files without valid styles', () => {
+    const code = [
+      'export const Button = () => <button className="button">Click</button>',
+      'Button.flair = `.a:::b { color: blue; }`',
+    ].join('\n')
+    const result = transformCode(code, 'misc-9.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.code).toBe(code)
+    expect(result.logs.map((log) => log.code)).toEqual(['css-parse-error'])
+
+    const next = transformCode('export const a = 1', 'misc-10.tsx', {
+      cssOutput: 'virtual',
+    })
+    expect(next).toBeFalsy()
+  })
+
+  test('async transform matches the sync transform', async () => {
+    const options = { cssOutput: 'virtual' as const }
+    const preprocessor = (css: string) => css.replace(/red/g, 'blue')
+
+    const [syncResult, asyncResult] = await Promise.all([
+      transformCode(combinedStylesContent, 'misc-11.tsx', options, preprocessor),
+      transformCodeAsync(combinedStylesContent, 'misc-11.tsx', options, preprocessor),
+    ])
+    if (!syncResult || !asyncResult) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(asyncResult.code).toBe(syncResult.code)
+    expect(asyncResult.css).toBe(syncResult.css)
+  })
+
+  test('batch transform returns results in order', async () => {
+    const options = { cssOutput: 'virtual' as const }
+    const files = [
+      { code: combinedStylesContent, filePath: 'misc-12.tsx' },
+      { code: 'export const a = 1', filePath: 'misc-13.tsx' },
+      { code: globalFlairStyleContent, filePath: 'misc-14.tsx' },
+    ]
+
+    const results = await transformMany(files, options)
+    expect(results).toHaveLength(3)
+    expect(results[0]?.css).toBe(transformCode(combinedStylesContent, 'misc-12.tsx', options)?.css)
+    expect(results[1]).toBeFalsy()
+    expect(results[2]?.css).toBe(transformCode(globalFlairStyleContent, 'misc-14.tsx', options)?.css)
+  })
+
+  test('component metadata is returned for every styled component', () => {
+    const result = transformCode(combinedStylesContent, 'misc-15.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+
+    const [box, secondBox] = result.components
+    expect(result.components.map((component) => component.name)).toEqual(['Box', 'SecondBox'])
+    expect(Object.keys(box.classNames).sort()).toEqual(['box', 'title'])
+    expect(result.code).toContain(box.classNames.title)
+    expect(box.scopedCss).toContain(`.${box.classNames.title}`)
+    expect(box.line).toBe(5)
+    expect(combinedStylesContent.slice(box.start, box.end)).toMatch(/^\(\{ children, containerClassName \}/)
+    expect(secondBox.classNames.box).not.toBe(box.classNames.box)
+    expect(secondBox.themeTokens).toEqual([])
+  })
+
+  test('namespace and default imports are working', () => {
+    const code = [
+      'import * as F from "@flairjs/client/react";',
+      'import flairClient from "@flairjs/client";',
+      'export const Button = () => (',
+      '  <>',
+      '    <button className={F.cn("button")}>Click</button>',
+      '    <span className={flairClient.c("label")}>Label</span>',
+      '    <F.Style>{`.label { color: blue; }`}</F.Style>',
+      '  </>',
+      ');',
+      'Button.flair = F.flair({ ".button": { color: "red" } });',
+    ].join('\n')
+    const result = transformCode(code, 'misc-16.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    const [component] = result.components
+    expect(result.code).toContain(`F.cn("${component?.classNames.button}")`)
+    expect(result.code).toContain(`flairClient.c("${component?.classNames.label}")`)
+    expect(result.code).not.toContain('F.Style')
+    expect(result.code).not.toContain('F.flair')
+    expect(result.css).toContain('color: red')
+  })
+
+  test('style tags are collected and removed across components, methods and expressions', () => {
+    const code = [
+      'import { Style } from "@flairjs/client/react";',
+      'import { Component } from "react";',
+      'export const A = ({ on }) => (',
+      '  <div>',
+      '    <p className="a"><Style>{`.a { color: red; }`}</Style></p>',
+      '    {on && <Style>{`.a2 { color: pink; }`}</Style>}',
+      '  </div>',
+      ');',
+      'export function B() {',
+      '  return <Style>{`.b { color: blue; }`}</Style>;',
+      '}',
+      'export class C extends Component {',
+      '  header() {',
+      '    return <h1 className="h"><Style>{`.h { color: black; }`}</Style></h1>;',
+      '  }',
+      '  render() {',
+      '    return <div className="c">{this.header()}<Style>{`.c { color: white; }`}</Style></div>;',
+      '  }',
+      '}',
+    ].join('\n')
+    const result = transformCode(code, 'misc-17.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.code).not.toContain('<Style')
+    expect(result.code).toContain('on && null')
+    expect(result.components.map((component) => Object.keys(component.classNames).sort())).toEqual([
+      ['a', 'a2'],
+      ['b'],
+      ['c', 'h'],
+    ])
+    expect(result.code).toContain(result.components[2]?.classNames.h)
+  })
+
+  test('static interpolations are evaluated and dynamic ones are reported', () => {
+    const code = [
+      'const SPACING = 4;',
+      'const COLORS = { primary: "#0070f3" } as const;',
+      'export const Button = ({ tone }) => <button className="button">Click</button>;',
+      'Button.flair = css`.button { padding: ${SPACING * 2}px; color: ${COLORS.primary}; }`;',
+      'export const Link = ({ tone }) => <a className="link">Click</a>;',
+      'Link.flair = css`.link { color: ${tone}; }`;',
+    ].join('\n')
+    const result = transformCode(code, 'misc-18.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.css).toContain('padding: 8px')
+    expect(result.css).toContain('color: #0070f3')
+    expect(result.code).not.toContain('Link.flair')
+    const [error] = result.logs
+    expect(error?.code).toBe('non-static-interpolation')
+    expect(error?.line).toBe(6)
+  })
+
+  test('style properties with runtime interpolations are removed from files without other styles', () => {
+    const code = [
+      'import { css } from "@flairjs/client";',
+      'export const Link = ({ tone }) => <a className="link">Click</a>;',
+      'Link.flair = css`.link { color: ${tone}; }`;',
+    ].join('\n')
+    const result = transformCode(code, 'misc-22.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.css).toBe('')
+    expect(result.code).not.toContain('Link.flair')
+    expect(result.logs.map((log) => log.code)).toEqual(['non-static-interpolation'])
+  })
+
+  test('static values of flair objects are evaluated', () => {
+    const code = [
+      'import { flair } from "@flairjs/client";',
+      'const SPACING = 4;',
+      'const COLOR = "#0070f3";',
+      'export const Card = () => <div className="card">Card</div>;',
+      'Card.flair = flair({ ".card": { padding: `${SPACING * 2}px`, color: COLOR, margin: -SPACING } });',
+    ].join('\n')
+    const result = transformCode(code, 'misc-23.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.css).toContain('padding: 8px')
+    expect(result.css).toContain('color: #0070f3')
+    expect(result.css).toContain('margin: -4')
+    expect(result.logs).toEqual([])
+  })
+
+  test('runtime values of flair objects are reported', () => {
+    const code = [
+      'import { flair } from "@flairjs/client";',
+      'export const Card = ({ size }) => <div className="card">Card</div>;',
+      'Card.flair = flair({ ".card": { padding: `${window.x}px` } });',
+      'export const Link = () => <a className="link">Link</a>;',
+      'Link.flair = flair({ ".link": { color: someVar } });',
+    ].join('\n')
+    const result = transformCode(code, 'misc-24.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.css).toBe('')
+    expect(result.code).not.toContain('.flair')
+    expect(result.logs.map((log) => [log.code, log.line])).toEqual([
+      ['non-static-interpolation', 3],
+      ['non-static-interpolation', 5],
+    ])
+  })
+
+  test('runtime values become custom properties set on the elements using the class', () => {
+    const code = [
+      'import { Style } from "@flairjs/client";',
+      'export const Button = ({ tone, size }) => {',
+      '  const border = tone === "dark" ? "black" : "white";',
+      '  return <button className="button" style={{ margin: 0 }}>',
+      '    <Style>{`.label { border-color: ${border}; }`}</Style>',
+      '    <span className="label">Click</span>',
+      '  </button>;',
+      '};',
+      'Button.flair = css`.button { color: ${(props) => props.tone}; padding: ${(props) => props.size}px; }`;',
+    ].join('\n')
+    const result = transformCode(code, 'misc-19.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.logs).toEqual([])
+    const properties = [...result.css.matchAll(/var\((--flair-[0-9a-f]+)\)/g)].map((match) => match[1])
+    expect(properties).toHaveLength(3)
+    for (const property of properties) {
+      expect(result.code).toContain(`"${property}"`)
+    }
+    expect(result.code).toContain('((props) => props.tone)(')
+    expect(result.code).toContain('}px`')
+    expect(result.code).toContain('margin: 0')
+  })
+
+  test('runtime values calling functions are bound without semantic information', () => {
+    const code = [
+      'import { Style } from "@flairjs/client";',
+      'import { fmt } from "./fmt";',
+      'export function Card({ tone }) {',
+      '  return <div className="card">',
+      '    <Style>{`.card { color: ${fmt(tone)}; border-color: ${function pick(p) { const c = p.tone; return c; }}; }`}</Style>',
+      '  </div>;',
+      '}',
+    ].join('\n')
+    const result = transformCode(code, 'misc-20.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.logs).toEqual([])
+    expect(result.code).toContain(': fmt(tone)')
+    expect(result.code).toContain('const c = p.tone;')
+    expect(result.css).toMatch(/color: var\(--flair-[0-9a-f]+\)/)
+  })
+
+  test('runtime values get the props the component does not destructure', () => {
+    const code = [
+      'export const Badge = ({ tone }) => <span className="badge">{tone}</span>;',
+      'Badge.flair = css`.badge { padding: ${(props) => props.size}px; }`;',
+      'export const Chip = ({ tone }) => <span className="chip">{tone}</span>;',
+      'Chip.flair = css`.chip { .icon:${(props) => props.state} { color: red; } }`;',
+    ].join('\n')
+    const result = transformCode(code, 'misc-21.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.code).toContain('...__flair_props__ }) =>')
+    expect(result.code).toContain('((props) => props.size)({')
+    const [error] = result.logs
+    expect(error?.code).toBe('non-static-interpolation')
+    expect(error?.line).toBe(4)
+  })
+
+  test('runtime values without an element using their class are reported', () => {
+    const code = [
+      'export const Tag = ({ tone, className }) => <span className={className}>{tone}</span>;',
+      'Tag.flair = css`.tag { color: ${(props) => props.tone}; }`;',
+    ].join('\n')
+    const result = transformCode(code, 'misc-25.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    const [warning] = result.logs
+    expect(warning?.code).toBe('unbound-runtime-value')
+    expect(warning?.level).toBe('warn')
+    expect(warning?.line).toBe(2)
+    expect(warning?.message).toContain('`.tag`')
+  })
 })
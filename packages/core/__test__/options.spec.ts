@@ -1,10 +1,11 @@
 import { describe, expect, test } from 'vitest'
 
-import { readFileSync } from 'node:fs'
+import { existsSync, mkdirSync, mkdtempSync, readFileSync, rmSync, statSync, writeFileSync } from 'node:fs'
+import { tmpdir } from 'node:os'
 import path from 'node:path'
 import { dirname } from 'path'
 import { fileURLToPath } from 'url'
-import { transformCode } from '../index'
+import { pruneCssFiles, readManifest, transformCode } from '../index'
 
 const __filename = fileURLToPath(import.meta.url)
 const __dirname = dirname(__filename)
@@ -23,4 +24,334 @@ describe('Options tests', () => {
     }
     expect(result.code).toMatchSnapshot()
   })
+
+  test('virtual css output is working', () => {
+    const result = transformCode(classNameListContent, 'options-2.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.virtualModuleId).toBe(`virtual:flair/${result.generatedCssName}`)
+    expect(result.code).toContain(`import "${result.virtualModuleId}"`)
+    expect(result.css).not.toBe('')
+  })
+
+  test('css import specifier and format are working', () => {
+    const cssOutDir = path.resolve(__dirname, './.css')
+    const result = transformCode(classNameListContent, path.resolve(__dirname, './snippets/options-3.tsx'), {
+      cssOutDir,
+      cssImport: {
+        specifier: 'relative',
+        format: 'cjs',
+      },
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.code).toContain(`require("../.css/${result.generatedCssName}")`)
+  })
+
+  test('css file name template is working', () => {
+    const options = {
+      cssOutDir: path.resolve(__dirname, './.css'),
+      cssFileName: '[dir]/[name].[contenthash].css',
+    }
+    const result = transformCode(classNameListContent, 'components/options-4.tsx', options)
+    const repeatedResult = transformCode(classNameListContent, 'components/options-4.tsx', options)
+    if (!result || !repeatedResult) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.generatedCssName).toMatch(/^components\/options-4\.[0-9a-f]{16}\.css$/)
+    expect(repeatedResult.generatedCssName).toBe(result.generatedCssName)
+  })
+
+  test('class name hashes are relative to the project root', () => {
+    const firstCheckout = transformCode(classNameListContent, '/home/first/project/src/options-5.tsx', {
+      cssOutput: 'virtual',
+      root: '/home/first/project',
+    })
+    const secondCheckout = transformCode(classNameListContent, '/ci/workspace/project/src/options-5.tsx', {
+      cssOutput: 'virtual',
+      root: '/ci/workspace/project',
+    })
+    if (!firstCheckout || !secondCheckout) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(secondCheckout.css).toBe(firstCheckout.css)
+    expect(secondCheckout.code).toBe(firstCheckout.code)
+  })
+
+  test('css modules pattern is working', () => {
+    const result = transformCode(classNameListContent, 'options-6.tsx', {
+      cssOutput: 'virtual',
+      classNameList: ['className', 'containerClassName'],
+      cssModules: {
+        pattern: '[local]',
+      },
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.code).toContain('containerClassName="item"')
+    expect(result.css).toContain('.box {')
+  })
+
+  test('targets and minify are working', () => {
+    const result = transformCode(classNameListContent, 'options-7.tsx', {
+      cssOutput: 'virtual',
+      classNameList: ['className', 'containerClassName'],
+      cssModules: {
+        pattern: '[local]',
+      },
+      targets: {
+        browserslist: ['safari >= 12'],
+        include: ['Nesting', 'VendorPrefixes'],
+      },
+      minify: true,
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.css).toContain('.box{padding:10px;border:1px solid #000}')
+  })
+
+  test('css source map is working', () => {
+    const code = [
+      'export const Button = () => <button className="button">Click</button>',
+      '',
+      'Button.flair = `',
+      '  .button {',
+      '    color: red;',
+      '  }',
+      '`',
+    ].join('\n')
+    const result = transformCode(code, 'options-8.tsx', {
+      cssOutput: 'virtual',
+      cssSourcemap: true,
+    })
+    if (!result?.cssSourcemap) {
+      throw new Error('transformCode did not return a css source map')
+    }
+    const map = JSON.parse(result.cssSourcemap)
+    expect(map.sources).toEqual(['options-8.tsx'])
+    expect(map.sourcesContent).toEqual([code])
+    // The first rule maps to line 4, column 3 of the source
+    expect(map.mappings.startsWith('AAGE')).toBe(true)
+  })
+
+  test('input source map is composed into the sourcemap', () => {
+    const lines = classNameListContent.split('\n')
+    // Maps every line of the input to the same line of `original.mdx`, shifted by 5 lines
+    const inputSourcemap = JSON.stringify({
+      version: 3,
+      sources: ['original.mdx'],
+      sourcesContent: ['# Original'],
+      names: [],
+      mappings: ['AAKA', ...lines.slice(1).map(() => 'AACA')].join(';'),
+    })
+    const result = transformCode(classNameListContent, 'options-9.tsx', {
+      cssOutput: 'virtual',
+      inputSourcemap,
+    })
+    if (!result?.sourcemap) {
+      throw new Error('transformCode did not return a sourcemap')
+    }
+    const map = JSON.parse(result.sourcemap)
+    expect(map.sources).toEqual(['original.mdx'])
+    expect(map.sourcesContent).toEqual(['# Original'])
+    expect(map.mappings).not.toBe('')
+  })
+
+  test('patch code output keeps the original formatting', () => {
+    const code = [
+      '// This comment is kept',
+      'export const Button = () => <button   className="button">Click</button>',
+      '',
+      'Button.flair = `',
+      '  .button { color: red; }',
+      '`',
+    ].join('\n')
+    const result = transformCode(code, 'options-10.tsx', {
+      cssOutput: 'virtual',
+      codeOutput: 'patch',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.code).toBe(
+      [
+        `import "${result.virtualModuleId}";// This comment is kept`,
+        'export const Button = () => <button   className="KUmW-q_button">Click</button>',
+        '',
+        '',
+        '',
+        '',
+      ].join('\n'),
+    )
+    expect(result.sourcemap).toBeDefined()
+  })
+
+  test('parse errors are reported and the original code is returned', () => {
+    const code = [
+      'export const Button = () => <button className="button">Click</button>',
+      'Button.flair = `.button { color: red; }`',
+      'const broken = (',
+    ].join('\n')
+    const result = transformCode(code, 'options-11.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.code).toBe(code)
+    expect(result.css).toBe('')
+    const [error] = result.logs
+    expect(error?.file).toBe('options-11.tsx')
+    expect(error?.level).toBe('error')
+    expect(error?.line).toBe(3)
+    expect(error?.frame).toContain('> 3 | const broken = (')
+  })
+
+  test('manifest is written and stale css files are pruned', () => {
+    const cssOutDir = mkdtempSync(path.join(tmpdir(), 'flair-manifest-'))
+    const sourcePath = path.resolve(__dirname, './snippets/options-class-name-list.tsx')
+    const options = { cssOutDir, root: __dirname, manifest: true }
+    const previous = transformCode(classNameListContent.replace('10px', '20px'), sourcePath, options)
+    const result = transformCode(classNameListContent, sourcePath, options)
+    if (!previous || !result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    const entry = readManifest(cssOutDir).files['snippets/options-class-name-list.tsx']
+    expect(entry?.cssFile).toBe(result.generatedCssName)
+    expect(entry?.previousCssFiles).toEqual([previous.generatedCssName])
+    expect(entry?.components.length).toBe(result.components.length)
+
+    // Files that weren't generated by a transform with a manifest are kept
+    writeFileSync(path.resolve(cssOutDir, 'other.css'), '.other { color: red; }')
+    expect(pruneCssFiles(cssOutDir, __dirname)).toEqual([previous.generatedCssName])
+    expect(existsSync(path.resolve(cssOutDir, previous.generatedCssName!))).toBe(false)
+    expect(existsSync(path.resolve(cssOutDir, result.generatedCssName!))).toBe(true)
+    expect(existsSync(path.resolve(cssOutDir, 'other.css'))).toBe(true)
+    expect(readManifest(cssOutDir).files['snippets/options-class-name-list.tsx']?.previousCssFiles).toEqual([])
+  })
+
+  test('pruning without a manifest removes nothing', () => {
+    const cssOutDir = mkdtempSync(path.join(tmpdir(), 'flair-manifest-'))
+    writeFileSync(path.resolve(cssOutDir, 'other.css'), '.other { color: red; }')
+    expect(readManifest(cssOutDir).files).toEqual({})
+    expect(pruneCssFiles(cssOutDir)).toEqual([])
+    expect(existsSync(path.resolve(cssOutDir, 'other.css'))).toBe(true)
+  })
+
+  test('css files of sources outside the root are not pruned', () => {
+    const cssOutDir = mkdtempSync(path.join(tmpdir(), 'flair-manifest-'))
+    const root = path.resolve(__dirname, './.css')
+    const result = transformCode(classNameListContent, path.resolve(__dirname, './snippets/options-class-name-list.tsx'), {
+      cssOutDir,
+      root,
+      manifest: true,
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(existsSync(path.resolve(cssOutDir, 'flair-manifest/_/snippets/options-class-name-list.tsx.json'))).toBe(true)
+    expect(pruneCssFiles(cssOutDir, root)).toEqual([])
+    expect(existsSync(path.resolve(cssOutDir, result.generatedCssName!))).toBe(true)
+  })
+
+  test('missing css out dir is created and unchanged css is not rewritten', () => {
+    const cssOutDir = path.resolve(__dirname, './.css/created/nested')
+    rmSync(path.resolve(__dirname, './.css/created'), { recursive: true, force: true })
+    const result = transformCode(classNameListContent, 'options-13.tsx', { cssOutDir })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    const cssFile = path.resolve(cssOutDir, result.generatedCssName!)
+    expect(readFileSync(cssFile, 'utf-8')).toBe(result.css)
+    const { mtimeMs } = statSync(cssFile)
+
+    transformCode(classNameListContent, 'options-13.tsx', { cssOutDir })
+    expect(statSync(cssFile).mtimeMs).toBe(mtimeMs)
+  })
+
+  test('css write errors are reported and the css import is left out', () => {
+    // A directory can't be created inside of a file
+    const cssOutDir = path.resolve(__dirname, './snippets/options-class-name-list.tsx/out')
+    const result = transformCode(classNameListContent, 'options-16.tsx', { cssOutDir })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    const [error] = result.logs
+    expect(error?.code).toBe('css-write-error')
+    expect(error?.level).toBe('error')
+    expect(result.generatedCssName).toBeFalsy()
+    expect(result.code).not.toContain('.css')
+    expect(result.css).not.toBe('')
+  })
+
+  test('css source map is removed when the css file can not be written', () => {
+    const cssOutDir = path.resolve(__dirname, './.css/unwritable')
+    // The css file can't replace a directory of the same name
+    mkdirSync(path.resolve(cssOutDir, 'options-17.css'), { recursive: true })
+    const result = transformCode(classNameListContent, 'options-17.tsx', {
+      cssOutDir,
+      cssFileName: 'options-17.css',
+      cssSourcemap: true,
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(result.logs.map((log) => log.code)).toEqual(['css-write-error'])
+    expect(existsSync(path.resolve(cssOutDir, 'options-17.css.map'))).toBe(false)
+  })
+
+  test('import sources and api aliases are working', () => {
+    const code = [
+      'import { Styles, cx } from "@acme/ui/styling/react";',
+      'export const Button = () => (',
+      '  <>',
+      '    <button className={cx("button")}>Click</button>',
+      '    <Styles>{`.label { color: blue; }`}</Styles>',
+      '  </>',
+      ');',
+      'Button.styles = `.button { color: red; }`;',
+      'Button.globalStyles = styled({ body: { margin: 0 } });',
+    ].join('\n')
+    const result = transformCode(code, 'options-14.tsx', {
+      cssOutput: 'virtual',
+      imports: {
+        sources: ['@acme/ui/styling'],
+        style: ['Styles'],
+        classNameUtils: ['cx'],
+        flairHelper: ['styled'],
+        flairProperty: ['styles'],
+        globalFlairProperty: ['globalStyles'],
+      },
+    })
+    if (!result) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    const [component] = result.components
+    expect(component?.classNames.button).toBeDefined()
+    expect(component?.classNames.label).toBeDefined()
+    expect(result.code).toContain(`cx("${component?.classNames.button}")`)
+    expect(result.code).not.toContain('<Styles>')
+    expect(result.code).not.toContain('Button.styles')
+    expect(result.css).toContain('margin: 0')
+  })
+
+  test('default css file name changes with the css', () => {
+    const first = transformCode('export const A = () => <div className="a" />;\nA.flair = `.a { color: red; }`;', 'options-15.tsx', {
+      cssOutput: 'virtual',
+    })
+    const second = transformCode('export const A = () => <div className="a" />;\nA.flair = `.a { color: blue; }`;', 'options-15.tsx', {
+      cssOutput: 'virtual',
+    })
+    if (!first || !second) {
+      throw new Error('transformCode returned null or undefined')
+    }
+    expect(first.generatedCssName).toMatch(/^[0-9a-f]{16}\.css$/)
+    expect(second.generatedCssName).not.toBe(first.generatedCssName)
+  })
 })
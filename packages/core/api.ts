@@ -1,3 +1,3 @@
-import { transformCode, TransformOptions, TransformOutput } from './index'
+import { ComponentMetadata, FlairImports, Manifest, ManifestComponent, ManifestEntry, pruneCssFiles, readManifest, transformCode, transformCodeAsync, transformMany, TransformFile, TransformOptions, TransformOutput } from './index'
 
-export { transformCode, TransformOptions, TransformOutput }
+export { ComponentMetadata, FlairImports, Manifest, ManifestComponent, ManifestEntry, pruneCssFiles, readManifest, transformCode, transformCodeAsync, transformMany, TransformFile, TransformOptions, TransformOutput }
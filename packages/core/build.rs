@@ -1,3 +1,6 @@
 fn main() {
-  napi_build::setup();
+  // Only the Node.js bindings need the napi linker setup
+  if std::env::var_os("CARGO_FEATURE_NAPI").is_some() {
+    napi_build::setup();
+  }
 }
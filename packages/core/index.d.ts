@@ -1,9 +1,102 @@
 /* auto-generated by NAPI-RS */
 /* eslint-disable */
+/** CSS Modules options used when scoping flair styles */
+export interface CssModulesOptions {
+  /**
+   * Class name pattern. Supports `[hash]`, `[local]`, `[name]` and `[content-hash]`.
+   * Defaults to `[hash]_[local]`. Use `[local]` to keep class names as written.
+   */
+  pattern?: string
+  /** Whether to scope animation names. Defaults to `true`. */
+  animation?: boolean
+  /** Whether to scope grid line and area names. Defaults to `true`. */
+  grid?: boolean
+  /** Whether to scope custom identifiers. Defaults to `true`. */
+  customIdents?: boolean
+  /** Whether to scope dashed identifiers, eg: custom properties. Defaults to `false`. */
+  dashedIdents?: boolean
+  /** Whether to scope container names. Defaults to `true`. */
+  container?: boolean
+}
+
+/** Browser targets and CSS features used when compiling flair styles */
+export interface CssTargets {
+  /** Browserslist queries, eg: `["safari >= 12", "> 0.5%"]`. Defaults to `["defaults"]`. */
+  browserslist?: Array<string>
+  /**
+   * Load the queries from the closest `.browserslistrc`, `browserslist` or
+   * `package.json#browserslist`, starting from the source file's directory.
+   * Ignored when `browserslist` is set.
+   */
+  discoverBrowserslist?: boolean
+  /**
+   * lightningcss features that are always compiled, eg: `["Nesting", "VendorPrefixes"]`.
+   * Defaults to `["Nesting", "MediaRangeSyntax"]`.
+   */
+  include?: Array<string>
+  /** lightningcss features that are never compiled, eg: `["LogicalProperties"]`. */
+  exclude?: Array<string>
+}
+
+/** Controls the import of the generated CSS that is added to every transformed file */
+export interface CssImport {
+  /**
+   * `template` (default) fills the `[file]` placeholder of `template`.
+   * `relative` imports the CSS file in `css_out_dir` relative to the source file.
+   * `absolute` imports the CSS file in `css_out_dir` by its absolute path.
+   */
+  specifier?: 'template' | 'relative' | 'absolute'
+  /**
+   * Specifier template, `[file]` is replaced with the generated CSS file name.
+   * Defaults to `@flairjs/client/generated-css/[file]`.
+   */
+  template?: string
+  /** `esm` (default) emits `import "..."`, `cjs` emits `require("...")`. */
+  format?: 'esm' | 'cjs'
+}
+
+/**
+ * Additional module sources and names of the flair APIs, eg: for a design system that re-exports
+ * them under its own package. The defaults are always recognised.
+ */
+export interface FlairImports {
+  /**
+   * Modules the flair APIs are re-exported from, eg: `["@acme/ui/styling"]`.
+   * Subpaths match as well, eg: `@acme/ui/styling/react`.
+   */
+  sources?: Array<string>
+  /** Names of the Style component, in addition to `Style` */
+  style?: Array<string>
+  /** Names of the class name utilities, in addition to `c` and `cn` */
+  classNameUtils?: Array<string>
+  /** Names of the object helper, in addition to `flair` */
+  flairHelper?: Array<string>
+  /** Names of the scoped style property, in addition to `flair` */
+  flairProperty?: Array<string>
+  /** Names of the global style property, in addition to `globalFlair` */
+  globalFlairProperty?: Array<string>
+}
+
 /** Log entry structure that will be sent to the JS side */
 export interface LogEntry {
   message: string
   level:  'error' | 'warn' | 'info'
+  /** Stable code of the problem, eg: `css-parse-error` */
+  code?: string
+  /** Path of the file the entry refers to */
+  file?: string
+  /** 1-based line of the location the entry refers to */
+  line?: number
+  /** 1-based column of the location the entry refers to */
+  column?: number
+  /** 1-based line where the location ends */
+  endLine?: number
+  /** 1-based column where the location ends (exclusive) */
+  endColumn?: number
+  /** Suggestion on how to fix the problem */
+  help?: string
+  /** Source lines around the location, with the location highlighted */
+  frame?: string
 }
 
 /** Log level enum that matches your required structure */
@@ -20,12 +113,166 @@ export interface Theme {
 
 export declare function transformCode(code: string, filePath: string, options: TransformOptions, cssPreprocessor?: (arg: string) => string | undefined | null): TransformOutput | null
 
+/**
+ * Same as `transformCode`, but parsing, CSS processing and codegen run on the libuv thread pool
+ * and the result is returned as a Promise, so the JS thread is free while files are transformed.
+ *
+ * `cssPreprocessor` is still called on the JS thread, one call at a time, while the worker waits for it.
+ * It must return a string synchronously, a returned Promise is not awaited.
+ */
+export declare function transformCodeAsync(code: string, filePath: string, options: TransformOptions, cssPreprocessor?: (arg: string) => string): Promise<TransformOutput | null>
+
+/** A source file passed to `transformMany` */
+export interface TransformFile {
+  code: string
+  filePath: string
+}
+
+/**
+ * Transforms many files in parallel with the same options, in a single call.
+ * Resolves to the result of every file, in the order of `files`.
+ *
+ * `cssPreprocessor` is called on the JS thread, like in `transformCodeAsync`.
+ */
+export declare function transformMany(files: Array<TransformFile>, options: TransformOptions, cssPreprocessor?: (arg: string) => string): Promise<Array<TransformOutput | null>>
+
+/**
+ * Removes the CSS files recorded in the `flair-manifest` directory of `css_out_dir` that are no longer
+ * used: the files of deleted source files and the earlier files of every source. Other files are kept.
+ * Returns the removed files, relative to `css_out_dir`, empty when there is no manifest.
+ */
+export declare function pruneCssFiles(cssOutDir: string, root?: string | undefined | null): Array<string>
+
+/**
+ * Reads the `flair-manifest` directory of `css_out_dir`, eg: to find the CSS file and class names of
+ * every source during server-side rendering. A missing manifest is empty.
+ */
+export declare function readManifest(cssOutDir: string): Manifest
+
+/**
+ * Build-wide record of the CSS generated for every source file, stored in the `flair-manifest`
+ * directory of `css_out_dir`.
+ */
+export interface Manifest {
+  /** Entries by source path, relative to the project root */
+  files: Record<string, ManifestEntry>
+}
+
+/** CSS generated for a source file */
+export interface ManifestEntry {
+  /**
+   * Path of the CSS file, relative to `css_out_dir`.
+   * `None` once the source has no styles, the entry is kept until its earlier files are pruned.
+   */
+  cssFile?: string
+  /** CSS files generated for the source by earlier builds, that weren't pruned yet */
+  previousCssFiles: Array<string>
+  /** xxh3 hash of the CSS */
+  contentHash: string
+  components: Array<ManifestComponent>
+}
+
+/** A component with styles */
+export interface ManifestComponent {
+  name?: string
+  /** Original to scoped class names */
+  classNames: Record<string, string>
+}
+
 export interface TransformOptions {
-  cssOutDir: string
+  /**
+   * Directory the generated CSS files are written to.
+   * Required when `css_output` is `file`.
+   */
+  cssOutDir?: string
   classNameList?: Array<string>
   useTheme?: boolean
   theme?: Theme
+  /**
+   * Appends a millisecond timestamp to the default CSS file name.
+   * Ignored when `css_file_name` is set, use `[contenthash]` instead.
+   */
   appendTimestampToCssFile?: boolean
+  /**
+   * `file` (default) writes the generated CSS to `css_out_dir`.
+   * `virtual` writes nothing to disk and imports the CSS through `virtual_module_id`.
+   */
+  cssOutput?: 'file' | 'virtual'
+  /**
+   * Module id template used for the CSS import when `css_output` is `virtual`.
+   * `[file]` is replaced with the generated CSS file name. Defaults to `virtual:flair/[file]`.
+   */
+  virtualModuleId?: string
+  /** Controls the specifier and module format of the generated CSS import */
+  cssImport?: CssImport
+  /**
+   * Naming template for the generated CSS file, relative to `css_out_dir`.
+   * Supports `[dir]`, `[name]`, `[ext]`, `[hash]` and `[contenthash]`. Defaults to `[contenthash].css`.
+   * Eg: `[dir]/[name].[contenthash].css`
+   */
+  cssFileName?: string
+  /**
+   * Project root, defaults to the current working directory.
+   * File paths are made relative to it before hashing so that generated
+   * class names and CSS file names are identical on every machine.
+   */
+  root?: string
+  /** Class name pattern and scoping options for CSS Modules */
+  cssModules?: CssModulesOptions
+  /** Browser targets and CSS features to compile the generated CSS for */
+  targets?: CssTargets
+  /** Minify the generated CSS. Defaults to `false`, expecting the bundler to handle minification. */
+  minify?: boolean
+  /**
+   * Generate a source map for the CSS that points back into the source file.
+   * When `css_output` is `file`, the map is written next to the CSS file as `{css_file_name}.map`.
+   */
+  cssSourcemap?: boolean
+  /**
+   * Source map of the input code from earlier transforms, as JSON.
+   * The returned `sourcemap` is composed with it, so it points to the original sources.
+   */
+  inputSourcemap?: string
+  /**
+   * `codegen` (default) reprints the whole program.
+   * `patch` applies the edits to the original source, keeping its formatting, comments and line numbers.
+   */
+  codeOutput?: 'codegen' | 'patch'
+  /**
+   * `bail` (default) returns the original code when it has syntax errors.
+   * `recover` transforms what the parser could recover. The errors are reported in `logs` either way.
+   */
+  onParseError?: 'bail' | 'recover'
+  /**
+   * Keep the `flair-manifest` directory in `css_out_dir` up to date with the CSS file and components of
+   * every transformed file, so stale CSS files can be removed with `prune_css_files`.
+   * Only used when `css_output` is `file`.
+   */
+  manifest?: boolean
+  /** Additional module sources and names the flair APIs are imported as */
+  imports?: FlairImports
+}
+
+/** Styles and class names of a component, see `TransformOutput.components` */
+export interface ComponentMetadata {
+  /** Name of the component, unless it is anonymous */
+  name?: string
+  /** Byte offset where the component function or class starts in the source */
+  start: number
+  /** Byte offset where the component function or class ends in the source */
+  end: number
+  /** 1-based line where the component starts */
+  line: number
+  /** 1-based column where the component starts */
+  column: number
+  /** Original to scoped class names, eg: `{ "button": "a1b2c3_button" }` */
+  classNames: Record<string, string>
+  /** Generated CSS of the scoped styles */
+  scopedCss?: string
+  /** Generated CSS of the global styles */
+  globalCss?: string
+  /** Theme tokens used by the styles, without the `$`, eg: `colors.primary` */
+  themeTokens: Array<string>
 }
 
 export interface TransformOutput {
@@ -34,4 +281,13 @@ export interface TransformOutput {
   css: string
   logs: Array<LogEntry>
   generatedCssName?: string
+  /**
+   * The module id imported by the transformed code when `css_output` is `virtual`.
+   * Bundlers should resolve this id to `css`.
+   */
+  virtualModuleId?: string
+  /** Source map of `css`, when `css_sourcemap` is enabled */
+  cssSourcemap?: string
+  /** Styles and class names of every component with styles, in source order */
+  components: Array<ComponentMetadata>
 }
@@ -509,4 +509,8 @@ if (!nativeBinding) {
 
 module.exports = nativeBinding
 module.exports.LogLevel = nativeBinding.LogLevel
+module.exports.pruneCssFiles = nativeBinding.pruneCssFiles
+module.exports.readManifest = nativeBinding.readManifest
 module.exports.transformCode = nativeBinding.transformCode
+module.exports.transformCodeAsync = nativeBinding.transformCodeAsync
+module.exports.transformMany = nativeBinding.transformMany
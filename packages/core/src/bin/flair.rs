@@ -0,0 +1,592 @@
+//! `flair` command line interface, built with the `cli` feature.
+//!
+//! Transforms files, globs or directories without a bundler and writes the transformed code,
+//! with the extracted CSS next to it, to an output directory. TypeScript and JSX are compiled to
+//! `.js` files (JSX with the automatic runtime), unless `--emit source` keeps them as they are.
+//!
+//! ```text
+//! flair src --out-dir dist --config flair.config.toml --watch
+//! ```
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use clap::{Parser, ValueEnum};
+use flair_core::css_output::{absolute_path, get_project_relative_path, write_file_atomic};
+use flair_core::{
+  transform_many, CssModulesOptions, CssTargets, FlairImports, Theme, TransformFile,
+  TransformOptions, TransformOutput,
+};
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use oxc::allocator::Allocator;
+use oxc::codegen::Codegen;
+use oxc::parser::Parser as OxcParser;
+use oxc::semantic::SemanticBuilder;
+use oxc::span::SourceType;
+use oxc::transformer::{
+  RewriteExtensionsMode, TransformOptions as OxcTransformOptions, Transformer, TypeScriptOptions,
+};
+use serde::Deserialize;
+
+/// Config files looked up in the current working directory when `--config` is not set
+const DEFAULT_CONFIG_FILES: [&str; 2] = ["flair.config.json", "flair.config.toml"];
+
+/// Extensions of the files picked up from directories
+const SOURCE_EXTENSIONS: [&str; 4] = ["tsx", "jsx", "ts", "js"];
+
+/// Directories that are skipped when walking a directory
+const IGNORED_DIRS: [&str; 1] = ["node_modules"];
+
+/// Changes that arrive within this window are rebuilt together
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+#[derive(Parser)]
+#[command(
+  name = "flair",
+  version,
+  about = "Transform flair styles without a bundler"
+)]
+struct Cli {
+  /// Files, directories or glob patterns to transform, eg: `src` or `"src/**/*.tsx"`
+  #[arg(required = true)]
+  inputs: Vec<String>,
+
+  /// Directory the transformed files and their CSS are written to
+  #[arg(short, long, default_value = "dist")]
+  out_dir: PathBuf,
+
+  /// JSON or TOML config file. Defaults to `flair.config.json` or `flair.config.toml` if present
+  #[arg(short, long)]
+  config: Option<PathBuf>,
+
+  /// Project root. Output paths and hashes are relative to it. Defaults to the current directory
+  #[arg(long)]
+  root: Option<PathBuf>,
+
+  /// Rebuild changed files until interrupted
+  #[arg(short, long)]
+  watch: bool,
+
+  /// What the transformed code is written as
+  #[arg(long, value_enum, default_value_t = Emit::Js)]
+  emit: Emit,
+}
+
+/// Format of the written code
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Emit {
+  /// JavaScript `.js` files, with TypeScript removed and JSX compiled with the automatic runtime
+  Js,
+  /// Files with the extension of the source, TypeScript and JSX still need to be compiled
+  Source,
+}
+
+/// Options read from the config file, with the same camelCase names as the JS options
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Config {
+  class_name_list: Option<Vec<String>>,
+  use_theme: Option<bool>,
+  theme: Option<Theme>,
+  targets: Option<CssTargets>,
+  css_modules: Option<CssModulesOptions>,
+  minify: Option<bool>,
+  css_sourcemap: Option<bool>,
+  code_output: Option<String>,
+  on_parse_error: Option<String>,
+  imports: Option<FlairImports>,
+}
+
+impl Config {
+  /// Reads a config file, the format is picked from its extension
+  fn load(path: &Path) -> Result<Self, String> {
+    let content = fs::read_to_string(path)
+      .map_err(|err| format!("Failed to read config {}: {}", path.display(), err))?;
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+      Some("toml") => toml::from_str(&content)
+        .map_err(|err| format!("Invalid config {}: {}", path.display(), err)),
+      _ => serde_json::from_str(&content)
+        .map_err(|err| format!("Invalid config {}: {}", path.display(), err)),
+    }
+  }
+
+  /// Builds the transform options. The CSS is imported relative to the transformed file,
+  /// and the CLI writes it next to it under `[name].[ext].css`. The extension keeps the CSS of
+  /// files with the same name apart, eg: `index.tsx` and `index.jsx`.
+  fn into_options(self, root: &Path) -> TransformOptions {
+    TransformOptions {
+      class_name_list: self.class_name_list,
+      use_theme: self.use_theme,
+      theme: self.theme,
+      targets: self.targets,
+      css_modules: self.css_modules,
+      minify: self.minify,
+      css_sourcemap: self.css_sourcemap,
+      code_output: self.code_output,
+      on_parse_error: self.on_parse_error,
+      imports: self.imports,
+      css_output: Some("virtual".to_string()),
+      virtual_module_id: Some("./[file]".to_string()),
+      css_file_name: Some("[name].[ext].css".to_string()),
+      root: Some(root.to_string_lossy().to_string()),
+      ..Default::default()
+    }
+  }
+}
+
+/// A command line input
+enum Input {
+  File(PathBuf),
+  Dir(PathBuf),
+  Glob(glob::Pattern),
+}
+
+impl Input {
+  fn parse(input: &str) -> Result<Self, String> {
+    let path = absolute_path(Path::new(input));
+    if path.is_dir() {
+      return Ok(Input::Dir(path));
+    }
+    if path.is_file() {
+      return Ok(Input::File(path));
+    }
+    if !input.contains(['*', '?', '[']) {
+      return Err(format!("Input not found: {}", input));
+    }
+
+    glob::Pattern::new(&path.to_string_lossy())
+      .map(Input::Glob)
+      .map_err(|err| format!("Invalid input {}: {}", input, err))
+  }
+
+  /// Source files currently matched by the input
+  fn files(&self, out_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    match self {
+      Input::File(path) => Ok(vec![path.clone()]),
+      Input::Dir(path) => {
+        let mut files = vec![];
+        walk_dir(path, out_dir, &mut files)?;
+        Ok(files)
+      }
+      Input::Glob(pattern) => {
+        let paths = glob::glob(pattern.as_str())
+          .map_err(|err| format!("Invalid glob {}: {}", pattern, err))?;
+        Ok(
+          paths
+            .filter_map(Result::ok)
+            .filter(|path| path.is_file() && !path.starts_with(out_dir))
+            .collect(),
+        )
+      }
+    }
+  }
+
+  /// Whether a changed path belongs to the input
+  fn matches(&self, path: &Path) -> bool {
+    match self {
+      Input::File(file) => file == path,
+      Input::Dir(dir) => {
+        path.starts_with(dir)
+          && is_source_file(path)
+          && !path
+            .strip_prefix(dir)
+            .unwrap_or(path)
+            .components()
+            .any(|component| is_ignored_dir(component.as_os_str().to_str()))
+      }
+      Input::Glob(pattern) => pattern.matches_path(path),
+    }
+  }
+
+  /// Path watched for changes of the input
+  fn watch_path(&self) -> (PathBuf, RecursiveMode) {
+    match self {
+      Input::File(path) => (path.clone(), RecursiveMode::NonRecursive),
+      Input::Dir(path) => (path.clone(), RecursiveMode::Recursive),
+      // The directory before the first wildcard, eg: `/project/src` for `/project/src/**/*.tsx`
+      Input::Glob(pattern) => {
+        let prefix: PathBuf = Path::new(pattern.as_str())
+          .components()
+          .take_while(|component| {
+            !component
+              .as_os_str()
+              .to_string_lossy()
+              .contains(['*', '?', '['])
+          })
+          .collect();
+        (prefix, RecursiveMode::Recursive)
+      }
+    }
+  }
+}
+
+fn is_source_file(path: &Path) -> bool {
+  path
+    .extension()
+    .and_then(|extension| extension.to_str())
+    .is_some_and(|extension| SOURCE_EXTENSIONS.contains(&extension))
+}
+
+fn is_ignored_dir(name: Option<&str>) -> bool {
+  name.is_some_and(|name| IGNORED_DIRS.contains(&name) || name.starts_with('.'))
+}
+
+/// Collects the source files under `dir`, skipping `out_dir`, `node_modules` and hidden directories
+fn walk_dir(dir: &Path, out_dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+  let entries =
+    fs::read_dir(dir).map_err(|err| format!("Failed to read {}: {}", dir.display(), err))?;
+
+  for entry in entries.flatten() {
+    let path = entry.path();
+    if path.is_dir() {
+      if path != out_dir && !is_ignored_dir(path.file_name().and_then(|name| name.to_str())) {
+        walk_dir(&path, out_dir, files)?;
+      }
+    } else if is_source_file(&path) {
+      files.push(path);
+    }
+  }
+
+  Ok(())
+}
+
+/// Shared state of a build
+struct Build {
+  root: PathBuf,
+  out_dir: PathBuf,
+  options: TransformOptions,
+  emit: Emit,
+  /// Files written for every source, so they can be removed when the source is deleted
+  /// or no longer produces them
+  outputs: RefCell<HashMap<PathBuf, Vec<PathBuf>>>,
+}
+
+impl Build {
+  /// Transforms `files` in parallel and writes the results to `out_dir`.
+  /// Files without styles are compiled like the others, or copied as they are with `--emit source`.
+  /// Returns `false` if anything failed.
+  fn run(&self, files: &[PathBuf]) -> bool {
+    let mut ok = true;
+    let mut sources = vec![];
+
+    for file in files {
+      match fs::read_to_string(file) {
+        Ok(code) => sources.push((file.to_string_lossy().to_string(), code)),
+        Err(err) => {
+          eprintln!("Failed to read {}: {}", file.display(), err);
+          ok = false;
+        }
+      }
+    }
+
+    let outputs = transform_many(
+      sources
+        .iter()
+        .map(|(file_path, code)| TransformFile {
+          code: code.clone(),
+          file_path: file_path.clone(),
+        })
+        .collect(),
+      &self.options,
+      None,
+    );
+
+    // Eg: `index.tsx` and `index.jsx` are both compiled to `index.js`
+    let mut sources_by_out_file: HashMap<PathBuf, &str> = HashMap::new();
+
+    for ((file_path, code), output) in sources.iter().zip(outputs) {
+      let mut out_file = self.out_dir.join(get_project_relative_path(
+        file_path,
+        Some(&self.root.to_string_lossy()),
+      ));
+      let compile = self.emit == Emit::Js && is_source_file(Path::new(file_path));
+      if compile {
+        out_file.set_extension("js");
+      }
+      if let Some(other) = sources_by_out_file.insert(out_file.clone(), file_path) {
+        eprintln!(
+          "{} and {} are both written to {}, use `--emit source` to keep their extensions",
+          other,
+          file_path,
+          out_file.display()
+        );
+        ok = false;
+        continue;
+      }
+
+      let result = match output {
+        Some(output) => {
+          for log in &output.logs {
+            eprintln!("[{}] {}", log.level, log);
+            ok &= log.level != "error";
+          }
+          write_output(&out_file, &output, file_path, compile)
+        }
+        None => write_code(&out_file, code, file_path, compile).map(|()| vec![out_file]),
+      };
+
+      match result {
+        Ok(written) => self.replace_outputs(Path::new(file_path), written),
+        Err(err) => {
+          eprintln!("{}", err);
+          ok = false;
+        }
+      }
+    }
+
+    ok
+  }
+
+  /// Records the files written for `source`, and removes the ones of the previous build it no
+  /// longer writes, eg: its CSS once it has no styles
+  fn replace_outputs(&self, source: &Path, written: Vec<PathBuf>) {
+    let previous = self
+      .outputs
+      .borrow_mut()
+      .insert(source.to_path_buf(), written.clone())
+      .unwrap_or_default();
+    for file in previous {
+      if !written.contains(&file) {
+        remove_output(&file);
+      }
+    }
+  }
+
+  /// Removes the files written for the sources under `path` that no longer exist
+  fn remove_deleted(&self, path: &Path) -> usize {
+    let mut outputs = self.outputs.borrow_mut();
+    let deleted: Vec<PathBuf> = outputs
+      .keys()
+      .filter(|source| source.starts_with(path) && !source.exists())
+      .cloned()
+      .collect();
+
+    for source in &deleted {
+      for file in outputs.remove(source).unwrap_or_default() {
+        remove_output(&file);
+      }
+    }
+    deleted.len()
+  }
+}
+
+fn remove_output(file: &Path) {
+  if let Err(err) = fs::remove_file(file) {
+    if err.kind() != std::io::ErrorKind::NotFound {
+      eprintln!("Failed to remove {}: {}", file.display(), err);
+    }
+  }
+}
+
+/// Writes the code of `file_path`, compiled to JS when `compile` is set
+fn write_code(out_file: &Path, code: &str, file_path: &str, compile: bool) -> Result<(), String> {
+  if compile {
+    write_file_atomic(out_file, &compile_to_js(code, Path::new(file_path))?)
+  } else {
+    write_file_atomic(out_file, code)
+  }
+}
+
+/// Writes the transformed code and its CSS, which the code imports as `./{generated_css_name}`.
+/// Returns the written files.
+fn write_output(
+  out_file: &Path,
+  output: &TransformOutput,
+  file_path: &str,
+  compile: bool,
+) -> Result<Vec<PathBuf>, String> {
+  write_code(out_file, &output.code, file_path, compile)?;
+  let mut written = vec![out_file.to_path_buf()];
+
+  let Some(css_name) = &output.generated_css_name else {
+    return Ok(written);
+  };
+  let css_file = out_file.with_file_name(css_name);
+  write_file_atomic(&css_file, &output.css)?;
+  written.push(css_file.clone());
+
+  if let Some(css_sourcemap) = &output.css_sourcemap {
+    let map_file = css_file.with_file_name(format!("{}.map", css_name));
+    write_file_atomic(&map_file, css_sourcemap)?;
+    written.push(map_file);
+  }
+
+  Ok(written)
+}
+
+/// Removes the TypeScript and compiles the JSX of the transformed code of `file_path`.
+/// Relative imports of `.ts` / `.tsx` files are rewritten to `.js`, like the written files.
+fn compile_to_js(code: &str, file_path: &Path) -> Result<String, String> {
+  let allocator = Allocator::default();
+  let source_type = SourceType::from_path(file_path)
+    .map_err(|err| format!("Failed to compile {}: {}", file_path.display(), err))?;
+  let parsed = OxcParser::new(&allocator, code, source_type).parse();
+  if let Some(err) = parsed.errors.first() {
+    return Err(format!(
+      "Failed to compile {}: {}",
+      file_path.display(),
+      err
+    ));
+  }
+
+  let mut program = parsed.program;
+  let scoping = SemanticBuilder::new()
+    .build(&program)
+    .semantic
+    .into_scoping();
+  let options = OxcTransformOptions {
+    typescript: TypeScriptOptions {
+      rewrite_import_extensions: Some(RewriteExtensionsMode::Rewrite),
+      ..Default::default()
+    },
+    ..Default::default()
+  };
+  let transformed =
+    Transformer::new(&allocator, file_path, &options).build_with_scoping(scoping, &mut program);
+  if let Some(err) = transformed.errors.first() {
+    return Err(format!(
+      "Failed to compile {}: {}",
+      file_path.display(),
+      err
+    ));
+  }
+
+  Ok(Codegen::new().build(&program).code)
+}
+
+/// Rebuilds the files that change under `inputs` until the process is interrupted
+fn watch(build: &Build, inputs: &[Input]) -> Result<(), String> {
+  let (sender, receiver) = mpsc::channel();
+  let mut watcher = notify::recommended_watcher(sender)
+    .map_err(|err| format!("Failed to start watching: {}", err))?;
+
+  for input in inputs {
+    let (path, mode) = input.watch_path();
+    watcher
+      .watch(&path, mode)
+      .map_err(|err| format!("Failed to watch {}: {}", path.display(), err))?;
+  }
+
+  eprintln!("Watching for changes...");
+
+  while let Ok(event) = receiver.recv() {
+    let mut changed = BTreeSet::new();
+    let mut collect = |event: notify::Result<Event>| {
+      // Reading the files for a rebuild emits access events, which must not trigger another one
+      if let Ok(event) = event {
+        // Renames report the old path too, which no longer exists and is handled like a removal
+        if matches!(
+          event.kind,
+          EventKind::Create(_)
+            | EventKind::Remove(_)
+            | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any)
+        ) {
+          changed.extend(event.paths);
+        }
+      }
+    };
+    collect(event);
+    // Editors often emit several events per save, rebuild them together
+    while let Ok(event) = receiver.recv_timeout(WATCH_DEBOUNCE) {
+      collect(event);
+    }
+
+    let (files, removed): (Vec<PathBuf>, Vec<PathBuf>) = changed
+      .into_iter()
+      .filter(|path| !path.starts_with(&build.out_dir))
+      .partition(|path| path.exists());
+
+    // Removed directories only report their own path, so every source under them is checked
+    let removed_count: usize = removed.iter().map(|path| build.remove_deleted(path)).sum();
+    if removed_count > 0 {
+      eprintln!("Removed the output of {} file(s)", removed_count);
+    }
+
+    let files: Vec<PathBuf> = files
+      .into_iter()
+      .filter(|path| path.is_file() && inputs.iter().any(|input| input.matches(path)))
+      .collect();
+    if !files.is_empty() {
+      build.run(&files);
+      eprintln!("Rebuilt {} file(s)", files.len());
+    }
+  }
+
+  Ok(())
+}
+
+fn run(cli: Cli) -> Result<bool, String> {
+  let root = match cli.root {
+    Some(root) => absolute_path(&root),
+    None => std::env::current_dir()
+      .map_err(|err| format!("Failed to get the current directory: {}", err))?,
+  };
+  let out_dir = absolute_path(&cli.out_dir);
+
+  let config_path = cli.config.or_else(|| {
+    DEFAULT_CONFIG_FILES
+      .iter()
+      .map(PathBuf::from)
+      .find(|path| path.is_file())
+  });
+  let config = match config_path {
+    Some(path) => Config::load(&path)?,
+    None => Config::default(),
+  };
+
+  let inputs = cli
+    .inputs
+    .iter()
+    .map(|input| Input::parse(input))
+    .collect::<Result<Vec<Input>, String>>()?;
+
+  let mut files = BTreeSet::new();
+  for (input, name) in inputs.iter().zip(&cli.inputs) {
+    let input_files = input.files(&out_dir)?;
+    // Most likely a typo, which would otherwise go unnoticed as an empty build
+    if input_files.is_empty() {
+      return Err(format!("No source files match the input: {}", name));
+    }
+    files.extend(input_files);
+  }
+  let files: Vec<PathBuf> = files.into_iter().collect();
+
+  let build = Build {
+    options: config.into_options(&root),
+    root,
+    out_dir,
+    emit: cli.emit,
+    outputs: RefCell::new(HashMap::new()),
+  };
+
+  let ok = build.run(&files);
+  eprintln!(
+    "Transformed {} file(s) into {}",
+    files.len(),
+    build.out_dir.display()
+  );
+
+  if cli.watch {
+    watch(&build, &inputs)?;
+  }
+
+  Ok(ok)
+}
+
+fn main() -> ExitCode {
+  match run(Cli::parse()) {
+    Ok(true) => ExitCode::SUCCESS,
+    Ok(false) => ExitCode::FAILURE,
+    Err(err) => {
+      eprintln!("{}", err);
+      ExitCode::FAILURE
+    }
+  }
+}
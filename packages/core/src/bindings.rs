@@ -0,0 +1,222 @@
+//! Node.js bindings, built with the `napi` feature.
+//! Options and results are the same structs as the Rust API, JS callbacks are adapted to [`CssPreprocessor`].
+
+use std::sync::mpsc;
+use std::time::Instant;
+
+use napi::bindgen_prelude::{AsyncTask, Env, Function};
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Status, Task};
+use napi_derive::napi;
+
+use crate::manifest;
+use crate::preprocessor::CssPreprocessor;
+use crate::transform::{self, TransformFile, TransformOptions, TransformOutput};
+
+/// A JS `css_preprocessor` callback passed to `transform_code`.
+/// The transform runs on the JS thread, so the callback is called directly.
+pub struct JsCssPreprocessor<'a> {
+  function: Function<'a, String, String>,
+}
+
+impl<'a> JsCssPreprocessor<'a> {
+  pub fn new(function: Function<'a, String, String>) -> Self {
+    Self { function }
+  }
+}
+
+impl CssPreprocessor for JsCssPreprocessor<'_> {
+  fn process(&self, css: &str) -> Result<String, String> {
+    self
+      .function
+      .call(css.to_string())
+      .map_err(|err| err.reason.clone())
+  }
+}
+
+/// JS callback that can be called from any thread, as passed to `transform_code_async`
+pub type ThreadsafeCssPreprocessorFn = ThreadsafeFunction<String, String, String, Status, false>;
+
+/// A JS `css_preprocessor` callback passed to `transform_code_async`.
+///
+/// The transform runs on the libuv thread pool, but JS can only run on the JS thread.
+/// Every call is queued to the JS thread and the worker waits for its result, so:
+/// - the callback must return a string synchronously, a returned Promise is not awaited
+/// - calls of one transform run one after another, in source order
+/// - the callback runs between other JS work, so it should be fast and must not block on the transform
+pub struct ThreadsafeCssPreprocessor {
+  function: ThreadsafeCssPreprocessorFn,
+}
+
+impl ThreadsafeCssPreprocessor {
+  pub fn new(function: ThreadsafeCssPreprocessorFn) -> Self {
+    Self { function }
+  }
+}
+
+impl CssPreprocessor for ThreadsafeCssPreprocessor {
+  fn process(&self, css: &str) -> Result<String, String> {
+    let (sender, receiver) = mpsc::channel();
+
+    let status = self.function.call_with_return_value(
+      css.to_string(),
+      ThreadsafeFunctionCallMode::Blocking,
+      move |result, _env| {
+        // The worker may have given up waiting, in which case the result is dropped
+        let _ = sender.send(result.map_err(|err| err.reason.clone()));
+        Ok(())
+      },
+    );
+
+    if status != Status::Ok {
+      return Err(format!("Failed to call the CSS preprocessor: {}", status));
+    }
+
+    receiver
+      .recv()
+      .map_err(|_| "The CSS preprocessor was released before it returned".to_string())?
+  }
+}
+
+#[napi]
+pub fn transform_code(
+  code: String,
+  file_path: String,
+  options: TransformOptions,
+  css_preprocessor: Option<Function<String, String>>,
+) -> Option<TransformOutput> {
+  let time = Instant::now();
+
+  // Example of using the logging system
+  if cfg!(debug_assertions) {
+    println!("Starting transformation for file: {}", file_path);
+  }
+
+  let css_preprocessor = css_preprocessor.map(JsCssPreprocessor::new);
+  let result = transform::transform(
+    code,
+    file_path,
+    options,
+    css_preprocessor
+      .as_ref()
+      .map(|preprocessor| preprocessor as &dyn CssPreprocessor),
+  );
+  let duration = time.elapsed();
+
+  if cfg!(debug_assertions) {
+    println!("Transformation completed in {:?}", duration);
+  }
+
+  result
+}
+
+/// Transform running on the libuv thread pool, see [`transform_code_async`]
+pub struct TransformTask {
+  code: String,
+  file_path: String,
+  options: Option<TransformOptions>,
+  css_preprocessor: Option<ThreadsafeCssPreprocessor>,
+}
+
+impl Task for TransformTask {
+  type Output = Option<TransformOutput>;
+  type JsValue = Option<TransformOutput>;
+
+  fn compute(&mut self) -> napi::Result<Self::Output> {
+    let Some(options) = self.options.take() else {
+      return Ok(None);
+    };
+
+    Ok(transform::transform(
+      std::mem::take(&mut self.code),
+      std::mem::take(&mut self.file_path),
+      options,
+      self
+        .css_preprocessor
+        .as_ref()
+        .map(|preprocessor| preprocessor as &dyn CssPreprocessor),
+    ))
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> napi::Result<Self::JsValue> {
+    Ok(output)
+  }
+}
+
+/// Same as [`transform_code`], but parsing, CSS processing and codegen run on the libuv thread pool
+/// and the result is returned as a Promise, so the JS thread is free while files are transformed.
+///
+/// `css_preprocessor` is still called on the JS thread, see [`ThreadsafeCssPreprocessor`].
+#[napi]
+pub fn transform_code_async(
+  code: String,
+  file_path: String,
+  options: TransformOptions,
+  css_preprocessor: Option<ThreadsafeCssPreprocessorFn>,
+) -> AsyncTask<TransformTask> {
+  AsyncTask::new(TransformTask {
+    code,
+    file_path,
+    options: Some(options),
+    css_preprocessor: css_preprocessor.map(ThreadsafeCssPreprocessor::new),
+  })
+}
+
+/// Batch of transforms running on the rayon thread pool, see [`transform_many`]
+pub struct TransformManyTask {
+  files: Vec<TransformFile>,
+  options: TransformOptions,
+  css_preprocessor: Option<ThreadsafeCssPreprocessor>,
+}
+
+impl Task for TransformManyTask {
+  type Output = Vec<Option<TransformOutput>>;
+  type JsValue = Vec<Option<TransformOutput>>;
+
+  fn compute(&mut self) -> napi::Result<Self::Output> {
+    Ok(transform::transform_many(
+      std::mem::take(&mut self.files),
+      &self.options,
+      self
+        .css_preprocessor
+        .as_ref()
+        .map(|preprocessor| preprocessor as &(dyn CssPreprocessor + Sync)),
+    ))
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> napi::Result<Self::JsValue> {
+    Ok(output)
+  }
+}
+
+/// Transforms many files in parallel with the same options, in a single call.
+/// Resolves to the result of every file, in the order of `files`.
+///
+/// `css_preprocessor` is called on the JS thread, like in [`transform_code_async`].
+#[napi]
+pub fn transform_many(
+  files: Vec<TransformFile>,
+  options: TransformOptions,
+  css_preprocessor: Option<ThreadsafeCssPreprocessorFn>,
+) -> AsyncTask<TransformManyTask> {
+  AsyncTask::new(TransformManyTask {
+    files,
+    options,
+    css_preprocessor: css_preprocessor.map(ThreadsafeCssPreprocessor::new),
+  })
+}
+
+/// Removes the CSS files recorded in the `flair-manifest` directory of `css_out_dir` that are no longer
+/// used: the files of deleted source files and the earlier files of every source. Other files are kept.
+/// Returns the removed files, relative to `css_out_dir`, empty when there is no manifest.
+#[napi]
+pub fn prune_css_files(css_out_dir: String, root: Option<String>) -> napi::Result<Vec<String>> {
+  manifest::prune_css_files(&css_out_dir, root.as_deref()).map_err(napi::Error::from_reason)
+}
+
+/// Reads the `flair-manifest` directory of `css_out_dir`, eg: to find the CSS file and class names of
+/// every source during server-side rendering. A missing manifest is empty.
+#[napi]
+pub fn read_manifest(css_out_dir: String) -> napi::Result<manifest::Manifest> {
+  manifest::Manifest::read(&css_out_dir).map_err(napi::Error::from_reason)
+}
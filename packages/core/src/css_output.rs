@@ -0,0 +1,326 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use xxhash_rust::xxh3::xxh3_64;
+
+/// Makes the temporary files of concurrent writes unique, see [`write_file_atomic`]
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Default module id used for the generated CSS in virtual mode.
+/// `[file]` is replaced with the generated CSS file name.
+pub const DEFAULT_VIRTUAL_MODULE_ID: &str = "virtual:flair/[file]";
+
+/// Default template used for the generated CSS import specifier.
+/// `[file]` is replaced with the generated CSS file name.
+pub const DEFAULT_CSS_IMPORT_TEMPLATE: &str = "@flairjs/client/generated-css/[file]";
+
+/// Default template used for the generated CSS file name.
+/// Content-hashed, so a changed stylesheet never reuses the name of a cached one.
+pub const DEFAULT_CSS_FILE_NAME: &str = "[contenthash].css";
+
+/// Controls how the extracted CSS leaves the transform.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum CssOutput {
+  /// Write the CSS to a file under `css_out_dir` (default)
+  File,
+  /// Write nothing to disk. The CSS is only returned in the transform output
+  /// and the transformed code imports it through a virtual module id.
+  Virtual,
+}
+
+impl CssOutput {
+  /// Resolves the output mode from the `css_output` option.
+  /// Unknown values fall back to [`CssOutput::File`].
+  pub fn from_option(value: Option<&str>) -> Self {
+    match value {
+      Some("virtual") => CssOutput::Virtual,
+      _ => CssOutput::File,
+    }
+  }
+}
+
+/// Controls how the specifier of the generated CSS import is built.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum CssImportSpecifier {
+  /// Fill the `[file]` placeholder of a template (default)
+  Template,
+  /// Relative path from the source file to the CSS file in `css_out_dir`
+  Relative,
+  /// Absolute path of the CSS file in `css_out_dir`
+  Absolute,
+}
+
+impl CssImportSpecifier {
+  /// Resolves the specifier kind from the `css_import.specifier` option.
+  /// Unknown values fall back to [`CssImportSpecifier::Template`].
+  pub fn from_option(value: Option<&str>) -> Self {
+    match value {
+      Some("relative") => CssImportSpecifier::Relative,
+      Some("absolute") => CssImportSpecifier::Absolute,
+      _ => CssImportSpecifier::Template,
+    }
+  }
+}
+
+/// Module format of the generated CSS import.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ModuleFormat {
+  /// `import "./abc123.css"` (default)
+  Esm,
+  /// `require("./abc123.css")`
+  Cjs,
+}
+
+impl ModuleFormat {
+  /// Resolves the module format from the `css_import.format` option.
+  /// Unknown values fall back to [`ModuleFormat::Esm`].
+  pub fn from_option(value: Option<&str>) -> Self {
+    match value {
+      Some("cjs") => ModuleFormat::Cjs,
+      _ => ModuleFormat::Esm,
+    }
+  }
+}
+
+/// Builds the generated CSS file name from a naming template.
+///
+/// Supported placeholders:
+/// - `[dir]` - directory of the source file, relative to the project root
+/// - `[name]` - source file name without its extension
+/// - `[ext]` - extension of the source file, without the dot
+/// - `[hash]` - hash of the project-relative source path, stable across builds
+/// - `[contenthash]` - hash of the project-relative source path and the emitted CSS
+///
+/// Eg: `[dir]/[name].[contenthash].css` -> `src/components/Button.5f1c2e9a0b3d4c6e.css`
+///
+/// All hashes are xxh3 over project-relative input, so names are identical on every machine.
+pub fn get_css_file_name(
+  template: Option<&str>,
+  source_file_path: &str,
+  root: Option<&str>,
+  css: &str,
+) -> String {
+  let relative_file_path = get_project_relative_path(source_file_path, root);
+  let relative_path = Path::new(&relative_file_path);
+
+  let dir = relative_path
+    .parent()
+    .map(to_import_path)
+    .unwrap_or_default();
+  let name = relative_path
+    .file_stem()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default();
+
+  let extension = relative_path
+    .extension()
+    .map(|extension| extension.to_string_lossy().to_string())
+    .unwrap_or_default();
+
+  let template = template.unwrap_or(DEFAULT_CSS_FILE_NAME);
+  let mut file_name = template
+    .replace("[dir]", &dir)
+    .replace("[name]", &name)
+    .replace("[ext]", &extension);
+
+  if file_name.contains("[hash]") {
+    let hash = xxh3_64(relative_file_path.as_bytes());
+    file_name = file_name.replace("[hash]", &format!("{:016x}", hash));
+  }
+
+  if file_name.contains("[contenthash]") {
+    let content = format!("{}\0{}", relative_file_path, css);
+    let content_hash = xxh3_64(content.as_bytes());
+    file_name = file_name.replace("[contenthash]", &format!("{:016x}", content_hash));
+  }
+
+  // An empty `[dir]` leaves stray separators behind, eg: `/Button.css` for files in the root
+  file_name
+    .split('/')
+    .filter(|segment| !segment.is_empty())
+    .collect::<Vec<&str>>()
+    .join("/")
+}
+
+/// Returns the path of `source_file_path` relative to the project `root`
+/// (the current working directory if not set), using forward slashes.
+///
+/// Parent directory segments are replaced with `_` so that names derived
+/// from it can never point outside of `css_out_dir`. Segments that only consist of underscores
+/// get one more, so the encoding stays reversible, eg: `../_/a.tsx` -> `_/__/a.tsx`.
+/// See [`resolve_project_relative_path`].
+pub fn get_project_relative_path(source_file_path: &str, root: Option<&str>) -> String {
+  let root = match root {
+    Some(root) => absolute_path(Path::new(root)),
+    None => std::env::current_dir().unwrap_or_default(),
+  };
+  let relative = relative_path(&root, &absolute_path(Path::new(source_file_path)));
+
+  relative
+    .components()
+    .filter(|component| !matches!(component, Component::CurDir))
+    .map(|component| match component {
+      Component::ParentDir => "_".to_string(),
+      other => {
+        let segment = other.as_os_str().to_string_lossy().to_string();
+        if !segment.is_empty() && segment.chars().all(|c| c == '_') {
+          format!("_{}", segment)
+        } else {
+          segment
+        }
+      }
+    })
+    .collect::<Vec<String>>()
+    .join("/")
+}
+
+/// Reverses [`get_project_relative_path`], returning the absolute path of the source file.
+pub fn resolve_project_relative_path(relative_path: &str, root: Option<&str>) -> PathBuf {
+  let mut path = match root {
+    Some(root) => absolute_path(Path::new(root)),
+    None => std::env::current_dir().unwrap_or_default(),
+  };
+
+  for segment in relative_path.split('/') {
+    if segment == "_" {
+      path.pop();
+    } else if !segment.is_empty() && segment.chars().all(|c| c == '_') {
+      path.push(&segment[1..]);
+    } else {
+      path.push(segment);
+    }
+  }
+  path
+}
+
+/// Builds the specifier used to import a generated CSS file from `source_file_path`.
+///
+/// Eg: for `src/components/Button.tsx` and `css_out_dir = src/generated`
+/// - `Template` -> `@flairjs/client/generated-css/abc123.css`
+/// - `Relative` -> `../generated/abc123.css`
+/// - `Absolute` -> `/project/src/generated/abc123.css`
+pub fn get_css_import_specifier(
+  kind: CssImportSpecifier,
+  template: Option<&str>,
+  source_file_path: &str,
+  css_out_dir: &str,
+  css_file_name: &str,
+) -> String {
+  match kind {
+    CssImportSpecifier::Template => template
+      .unwrap_or(DEFAULT_CSS_IMPORT_TEMPLATE)
+      .replace("[file]", css_file_name),
+    CssImportSpecifier::Absolute => {
+      to_import_path(&absolute_path(Path::new(css_out_dir)).join(css_file_name))
+    }
+    CssImportSpecifier::Relative => {
+      let css_file = absolute_path(Path::new(css_out_dir)).join(css_file_name);
+      let source_dir = absolute_path(Path::new(source_file_path))
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+      let relative = to_import_path(&relative_path(&source_dir, &css_file));
+      // Bare specifiers would be resolved as packages, so always start with a dot
+      if relative.starts_with("../") {
+        relative
+      } else {
+        format!("./{}", relative)
+      }
+    }
+  }
+}
+
+/// Resolves `path` against the current working directory if it is relative.
+pub fn absolute_path(path: &Path) -> PathBuf {
+  if path.is_absolute() {
+    return path.to_path_buf();
+  }
+
+  std::env::current_dir()
+    .map(|cwd| cwd.join(path))
+    .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Computes the path of `to` relative to the directory `from`.
+/// Both paths are expected to be absolute.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+  let from_components: Vec<Component> = from.components().collect();
+  let to_components: Vec<Component> = to.components().collect();
+
+  let common_length = from_components
+    .iter()
+    .zip(to_components.iter())
+    .take_while(|(a, b)| a == b)
+    .count();
+
+  let mut relative = PathBuf::new();
+  for _ in common_length..from_components.len() {
+    relative.push("..");
+  }
+  for component in &to_components[common_length..] {
+    relative.push(component.as_os_str());
+  }
+
+  relative
+}
+
+/// Converts a file system path to a module specifier, which always uses forward slashes.
+fn to_import_path(path: &Path) -> String {
+  path.to_string_lossy().replace('\\', "/")
+}
+
+/// Builds the virtual module id for a generated CSS file.
+///
+/// Eg: `virtual:flair/[file]` + `abc123.css` -> `virtual:flair/abc123.css`
+pub fn get_virtual_module_id(template: Option<&str>, css_file_name: &str) -> String {
+  template
+    .unwrap_or(DEFAULT_VIRTUAL_MODULE_ID)
+    .replace("[file]", css_file_name)
+}
+
+/// Writes the CSS to `{css_out_dir}/{css_file_name}`.
+/// `css_out_dir` and directories that are part of `css_file_name` (eg: from a `[dir]` placeholder)
+/// are created when missing. See [`write_file_atomic`] for how the file is written.
+pub fn write_css_file(css_out_dir: &str, css_file_name: &str, css: &str) -> Result<(), String> {
+  write_file_atomic(&Path::new(css_out_dir).join(css_file_name), css)
+}
+
+/// Writes `content` to `path` through a temporary file in the same directory that is renamed
+/// over `path`, so file watchers never see a half-written file. Missing parent directories are
+/// created. Nothing is written when the file already has the same content, so unchanged files
+/// keep their modification time and don't trigger rebuilds.
+pub fn write_file_atomic(path: &Path, content: &str) -> Result<(), String> {
+  if fs::read(path).is_ok_and(|existing| existing == content.as_bytes()) {
+    return Ok(());
+  }
+
+  let dir = path.parent().unwrap_or(Path::new("."));
+  fs::create_dir_all(dir).map_err(|err| {
+    format!(
+      "Failed to create directory: {}, reason: {}",
+      dir.display(),
+      err
+    )
+  })?;
+
+  let file_name = path
+    .file_name()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default();
+  // Unique per process and write, parallel transforms may write the same file
+  let temp_path = dir.join(format!(
+    ".{}.{}.{}.tmp",
+    file_name,
+    std::process::id(),
+    TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+  ));
+
+  fs::write(&temp_path, content)
+    .and_then(|_| fs::rename(&temp_path, path))
+    .map_err(|err| {
+      let _ = fs::remove_file(&temp_path);
+      format!("Failed to write file: {}, reason: {}", path.display(), err)
+    })
+}
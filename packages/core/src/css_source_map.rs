@@ -0,0 +1,223 @@
+use std::path::Path;
+
+use parcel_sourcemap::{Mapping, OriginalLocation, SourceMap};
+
+/// Maps byte offsets of an extracted CSS string back to byte offsets in the source file.
+///
+/// Extracted CSS is stitched together from one or more pieces of the source file,
+/// eg: the quasis of a template literal or the children of a `<Style>` element.
+/// Every piece is recorded as a segment starting at `css_offset`.
+#[derive(Clone, Debug, Default)]
+pub struct CssSourceMapping {
+  segments: Vec<CssSourceSegment>,
+}
+
+#[derive(Clone, Debug)]
+struct CssSourceSegment {
+  /// Byte offset in the extracted CSS where the segment starts
+  css_offset: usize,
+  /// Byte offset in the source file where the segment starts
+  source_offset: u32,
+  /// `true` if the CSS was copied as-is from the source, so offsets within the segment map 1:1.
+  /// Generated CSS (eg: from a `flair({...})` object) maps every offset to `source_offset`.
+  verbatim: bool,
+}
+
+impl CssSourceMapping {
+  /// CSS copied as-is from the source file, starting at `source_offset`
+  pub fn verbatim(source_offset: u32) -> Self {
+    let mut mapping = Self::default();
+    mapping.push_verbatim(0, source_offset);
+    mapping
+  }
+
+  /// CSS generated from the source file, every position maps to `source_offset`
+  pub fn generated(source_offset: u32) -> Self {
+    Self {
+      segments: vec![CssSourceSegment {
+        css_offset: 0,
+        source_offset,
+        verbatim: false,
+      }],
+    }
+  }
+
+  pub fn push_verbatim(&mut self, css_offset: usize, source_offset: u32) {
+    self.segments.push(CssSourceSegment {
+      css_offset,
+      source_offset,
+      verbatim: true,
+    });
+  }
+
+  /// CSS generated from the source file from `css_offset` on, eg: an interpolated value.
+  /// Every position of the segment maps to `source_offset`.
+  pub fn push_generated(&mut self, css_offset: usize, source_offset: u32) {
+    self.segments.push(CssSourceSegment {
+      css_offset,
+      source_offset,
+      verbatim: false,
+    });
+  }
+
+  /// Appends the segments of `other`, whose CSS was appended at `css_offset`
+  pub fn extend(&mut self, other: &CssSourceMapping, css_offset: usize) {
+    self
+      .segments
+      .extend(other.segments.iter().map(|segment| CssSourceSegment {
+        css_offset: segment.css_offset + css_offset,
+        ..segment.clone()
+      }));
+  }
+
+  /// Maps every position to the start of the CSS.
+  /// Used when the CSS was rewritten (eg: by a preprocessor) and offsets can no longer be traced.
+  pub fn collapse(&self) -> Self {
+    match self.segments.first() {
+      Some(segment) => Self::generated(segment.source_offset),
+      None => Self::default(),
+    }
+  }
+
+  /// Returns the source file offset of the CSS at `css_offset`
+  pub fn source_offset(&self, css_offset: usize) -> Option<u32> {
+    let segment = self
+      .segments
+      .iter()
+      .rev()
+      .find(|segment| segment.css_offset <= css_offset)?;
+
+    if segment.verbatim {
+      Some(segment.source_offset + (css_offset - segment.css_offset) as u32)
+    } else {
+      Some(segment.source_offset)
+    }
+  }
+}
+
+/// Converts between byte offsets and line / column positions of a text.
+/// Columns are counted in UTF-16 code units, like in source maps and lightningcss locations.
+pub struct LineIndex<'a> {
+  text: &'a str,
+  line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+  pub fn new(text: &'a str) -> Self {
+    let line_starts = std::iter::once(0)
+      .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+      .collect();
+
+    Self { text, line_starts }
+  }
+
+  /// Returns the byte offset of a 0-based line and column
+  pub fn offset(&self, line: u32, column: u32) -> usize {
+    let Some(line_start) = self.line_starts.get(line as usize) else {
+      return self.text.len();
+    };
+
+    let mut utf16_column = 0;
+    for (index, ch) in self.text[*line_start..].char_indices() {
+      if utf16_column >= column as usize || ch == '\n' {
+        return line_start + index;
+      }
+      utf16_column += ch.len_utf16();
+    }
+
+    self.text.len()
+  }
+
+  /// Returns the 0-based line and column of a byte offset
+  pub fn position(&self, offset: usize) -> (u32, u32) {
+    let offset = offset.min(self.text.len());
+    let line = self
+      .line_starts
+      .partition_point(|line_start| *line_start <= offset)
+      .saturating_sub(1);
+    let line_start = self.line_starts[line];
+
+    let column: usize = self
+      .text
+      .get(line_start..offset)
+      .map(|text| text.chars().map(char::len_utf16).sum())
+      .unwrap_or(0);
+
+    (line as u32, column as u32)
+  }
+}
+
+/// Rewrites the mappings of a lightningcss source map, whose original positions point into `css`,
+/// so that they point into the source file the CSS was extracted from.
+///
+/// Theme tokens are replaced before lightningcss parses the CSS,
+/// so columns on lines that contain theme tokens are approximate.
+pub fn remap_css_mappings(
+  css_map: &SourceMap,
+  css: &str,
+  mapping: &CssSourceMapping,
+  source: &LineIndex,
+) -> Vec<Mapping> {
+  let css_index = LineIndex::new(css);
+
+  css_map
+    .get_mappings()
+    .into_iter()
+    .filter_map(|css_mapping| {
+      let original = css_mapping.original?;
+      let css_offset = css_index.offset(original.original_line, original.original_column);
+      let source_offset = mapping.source_offset(css_offset)?;
+      let (original_line, original_column) = source.position(source_offset as usize);
+
+      Some(Mapping {
+        generated_line: css_mapping.generated_line,
+        generated_column: css_mapping.generated_column,
+        original: Some(OriginalLocation {
+          original_line,
+          original_column,
+          source: 0,
+          name: None,
+        }),
+      })
+    })
+    .collect()
+}
+
+/// Builds the source map of the generated CSS file, which is `css_chunks` joined with new lines.
+///
+/// # Arguments
+/// * `css_chunks` - CSS of every processed component, in output order
+/// * `chunk_mappings` - Remapped mappings of every chunk, relative to the chunk
+/// * `source_path` - Path of the source file
+/// * `source_text` - Content of the source file, embedded as `sourcesContent`
+/// * `map_dir` - Absolute directory the source map is served from. Sources are relative to it.
+pub fn build_css_source_map(
+  css_chunks: &[String],
+  chunk_mappings: &[Vec<Mapping>],
+  source_path: &str,
+  source_text: &str,
+  map_dir: &Path,
+) -> Result<String, String> {
+  let mut source_map = SourceMap::new(&map_dir.to_string_lossy());
+  let source_index = source_map.add_source(source_path);
+  source_map
+    .set_source_content(source_index as usize, source_text)
+    .map_err(|err| format!("Failed to build CSS source map: {}", err))?;
+
+  let mut line_offset = 0;
+  for (css, mappings) in css_chunks.iter().zip(chunk_mappings) {
+    for mapping in mappings {
+      source_map.add_mapping(
+        mapping.generated_line + line_offset,
+        mapping.generated_column,
+        mapping.original,
+      );
+    }
+    // Chunks are joined with a new line
+    line_offset += css.matches('\n').count() as u32 + 1;
+  }
+
+  source_map
+    .to_json(None)
+    .map_err(|err| format!("Failed to build CSS source map: {}", err))
+}
@@ -0,0 +1,534 @@
+use std::collections::HashSet;
+
+use oxc::allocator::Allocator;
+use oxc::ast::ast::{
+  BindingPattern, BindingPatternKind, Expression, FormalParameters, JSXAttributeItem,
+  JSXAttributeName, JSXAttributeValue, JSXExpression, JSXOpeningElement, ObjectPattern,
+  ObjectPropertyKind, SourceType, StringLiteral, TemplateElement,
+};
+use oxc::ast::{AstBuilder, NONE};
+use oxc::ast_visit::{walk_mut, Visit, VisitMut};
+use oxc::parser::Parser;
+use oxc::span::{GetSpan, Span, SPAN};
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::patch::Patches;
+use crate::update_attribute::is_class_name_attribute;
+
+/// An interpolation whose value is only known at runtime, eg: `${props => props.tone}` or `${color}`.
+///
+/// It is replaced with `var(--flair-...)` in the CSS, and the value is set as that custom property
+/// on the elements of the component that use the classes of its rule, eg:
+/// `<button className="btn" style={{ "--flair-1a2b3c4d": (props => props.tone)(props) }}>`
+#[derive(Debug, Clone)]
+pub struct DynamicValue {
+  /// Name of the custom property, eg: `--flair-1a2b3c4d`
+  pub name: String,
+  /// Span of the interpolated expression
+  pub span: Span,
+  /// Whether the expression is a function that is called with the component props
+  pub is_function: bool,
+  /// Unit written right after the interpolation, eg: `px` in `${size}px`. It becomes part of the value.
+  pub unit: String,
+  /// Class names of the selectors of the rule, and its parent rules, that the value is used in
+  pub class_names: Vec<String>,
+}
+
+/// Collects the runtime values of the styles of a file
+pub struct DynamicValues<'s> {
+  /// Project-relative path of the file, keeps the custom property names of files apart
+  file_id: &'s str,
+  /// Only accept functions of the props. Style properties are assigned outside of the component,
+  /// so any other expression could mean something else where the value is set.
+  functions_only: bool,
+  pub values: Vec<DynamicValue>,
+}
+
+impl<'s> DynamicValues<'s> {
+  pub fn new(file_id: &'s str, functions_only: bool) -> Self {
+    Self {
+      file_id,
+      functions_only,
+      values: vec![],
+    }
+  }
+
+  /// Registers `expression` as a runtime value, given the CSS that comes before and after it.
+  ///
+  /// Returns why it can't be one otherwise, eg: when the interpolation isn't in a declaration
+  /// value of a rule with a class selector, as a custom property can't be used anywhere else.
+  pub fn add(
+    &mut self,
+    expression: &Expression,
+    css_before: &str,
+    css_after: &str,
+  ) -> Result<&DynamicValue, &'static str> {
+    let is_function = matches!(
+      expression.without_parentheses(),
+      Expression::ArrowFunctionExpression(_) | Expression::FunctionExpression(_)
+    );
+    if self.functions_only && !is_function {
+      return Err("runtime values of style properties must be functions of the props, eg: `${props => props.tone}`");
+    }
+    let Some(class_names) = get_rule_class_names(css_before, css_after) else {
+      return Err(
+        "runtime values can only be used in the declaration values of rules with a class selector",
+      );
+    };
+
+    let span = expression.span();
+    let hash = xxh3_64(format!("{}:{}", self.file_id, span.start).as_bytes());
+    let unit_length = css_after
+      .find(|c: char| !(c.is_ascii_alphabetic() || c == '%'))
+      .unwrap_or(css_after.len());
+
+    self.values.push(DynamicValue {
+      name: format!("--flair-{:08x}", hash as u32),
+      span,
+      is_function,
+      unit: css_after[..unit_length].to_string(),
+      class_names,
+    });
+    Ok(&self.values[self.values.len() - 1])
+  }
+}
+
+/// Returns the class names of the rules enclosing the end of `css`, if it ends in a declaration value.
+/// `css_after` is the CSS that follows, it tells nested rule preludes apart from declarations.
+///
+/// Eg: `.card { &:hover { color: ` -> `Some(["card"])`, `.card { ` -> `None`,
+/// `.card { .btn:` followed by ` { ...` -> `None`
+fn get_rule_class_names(css: &str, css_after: &str) -> Option<Vec<String>> {
+  // `.card { .btn:${state} { ... } }` is a selector, even though it looks like `property: value`
+  if css_after
+    .find(['{', ';', '}'])
+    .is_some_and(|index| css_after.as_bytes()[index] == b'{')
+  {
+    return None;
+  }
+
+  let bytes = css.as_bytes();
+  // Preludes of the rules that are open at the end of `css`, eg: `.card` and `&:hover`
+  let mut preludes: Vec<&str> = vec![];
+  let mut segment_start = 0;
+  let mut index = 0;
+
+  while index < bytes.len() {
+    match bytes[index] {
+      quote @ (b'"' | b'\'') => {
+        index += 1;
+        while index < bytes.len() && bytes[index] != quote {
+          if bytes[index] == b'\\' {
+            index += 1;
+          }
+          index += 1;
+        }
+        if index >= bytes.len() {
+          // Inside a string, eg: `content: "${label}"`
+          return None;
+        }
+      }
+      b'/' if bytes.get(index + 1) == Some(&b'*') => match css[index + 2..].find("*/") {
+        Some(end) => index += end + 3,
+        None => return None,
+      },
+      b'{' => {
+        preludes.push(css[segment_start..index].trim());
+        segment_start = index + 1;
+      }
+      b'}' => {
+        preludes.pop();
+        segment_start = index + 1;
+      }
+      b';' => segment_start = index + 1,
+      _ => {}
+    }
+    index += 1;
+  }
+
+  // A declaration is `property: value`, at-rule preludes such as `@media (width: ...)` don't count
+  let declaration = css[segment_start..].trim_start();
+  if preludes.is_empty() || !declaration.contains(':') || declaration.starts_with('@') {
+    return None;
+  }
+
+  let mut class_names: Vec<String> = vec![];
+  for prelude in preludes.iter().filter(|prelude| !prelude.starts_with('@')) {
+    let mut rest = *prelude;
+    while let Some(position) = rest.find('.') {
+      rest = &rest[position + 1..];
+      let end = rest
+        .find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or(rest.len());
+      let class_name = &rest[..end];
+      if !class_name.is_empty()
+        && !class_name.starts_with(|c: char| c.is_ascii_digit())
+        && !class_names.iter().any(|existing| existing == class_name)
+      {
+        class_names.push(class_name.to_string());
+      }
+    }
+  }
+
+  if class_names.is_empty() {
+    None
+  } else {
+    Some(class_names)
+  }
+}
+
+/// Name of the rest element added to destructured props, see [`bind_props`]
+const PROPS_REST_NAME: &str = "__flair_props__";
+
+/// Makes every prop of a component available to its function values, which may read props that
+/// the component doesn't destructure. Destructured props without a rest element get one, and a
+/// component without parameters gets a props parameter.
+///
+/// Eg: `({ tone }) => ...` -> `({ tone, ...__flair_props__ }) => ...`
+pub fn bind_props<'a>(
+  ast_builder: AstBuilder<'a>,
+  params: &mut FormalParameters<'a>,
+  source_text: &str,
+  patches: &mut Patches,
+) {
+  let Some(param) = params.items.first_mut() else {
+    // `function Card() {` -> `function Card(__flair_props__) {`
+    if params.span.is_empty() {
+      return;
+    }
+    patches.insert(params.span.start + 1, PROPS_REST_NAME.to_string());
+    let pattern = ast_builder.binding_pattern(
+      ast_builder.binding_pattern_kind_binding_identifier(SPAN, PROPS_REST_NAME),
+      NONE,
+      false,
+    );
+    params.items.push(ast_builder.formal_parameter(
+      SPAN,
+      ast_builder.vec(),
+      pattern,
+      None,
+      false,
+      false,
+    ));
+    return;
+  };
+
+  let Some(object) = get_object_pattern(&mut param.pattern) else {
+    return;
+  };
+  if object.rest.is_some() {
+    return;
+  }
+
+  // The rest element has to be last, after a trailing comma if there is one
+  match object.properties.last() {
+    Some(property) => {
+      // The span of the pattern includes its type annotation, eg: `{ tone }: Props`
+      let after = &source_text[property.span.end as usize..object.span.end as usize];
+      let Some(close) = after.find('}') else {
+        return;
+      };
+      if after[..close].contains(',') {
+        patches.insert(
+          property.span.end + close as u32,
+          format!("...{} ", PROPS_REST_NAME),
+        );
+      } else {
+        patches.insert(property.span.end, format!(", ...{}", PROPS_REST_NAME));
+      }
+    }
+    None => patches.insert(object.span.start + 1, format!("...{}", PROPS_REST_NAME)),
+  }
+  object.rest = Some(ast_builder.alloc_binding_rest_element(
+    SPAN,
+    ast_builder.binding_pattern(
+      ast_builder.binding_pattern_kind_binding_identifier(SPAN, PROPS_REST_NAME),
+      NONE,
+      false,
+    ),
+  ));
+}
+
+/// The object pattern of destructured props, including ones with a default, eg: `({ tone } = {})`
+fn get_object_pattern<'p, 'a>(
+  pattern: &'p mut BindingPattern<'a>,
+) -> Option<&'p mut ObjectPattern<'a>> {
+  match &mut pattern.kind {
+    BindingPatternKind::ObjectPattern(object) => Some(object),
+    BindingPatternKind::AssignmentPattern(assignment) => get_object_pattern(&mut assignment.left),
+    _ => None,
+  }
+}
+
+/// Code of the props of a component, passed to function values.
+///
+/// Eg: `(props) => ...` -> `props`, `({ tone, size: s = 2 }) => ...` -> `{ tone, "size": s }`
+pub fn get_props_code(params: &FormalParameters) -> String {
+  let Some(param) = params.items.first() else {
+    return "{}".to_string();
+  };
+
+  get_pattern_code(&param.pattern).unwrap_or_else(|| "{}".to_string())
+}
+
+fn get_pattern_code(pattern: &BindingPattern) -> Option<String> {
+  match &pattern.kind {
+    BindingPatternKind::BindingIdentifier(identifier) => Some(identifier.name.to_string()),
+    BindingPatternKind::AssignmentPattern(assignment) => get_pattern_code(&assignment.left),
+    // Destructured props are put back together from their bindings
+    BindingPatternKind::ObjectPattern(object) => {
+      let mut properties = vec![];
+      if let Some(rest) = &object.rest {
+        if let BindingPatternKind::BindingIdentifier(identifier) = &rest.argument.kind {
+          properties.push(format!("...{}", identifier.name));
+        }
+      }
+      for property in &object.properties {
+        let (Some(key), false) = (property.key.static_name(), property.computed) else {
+          continue;
+        };
+        let local = match &property.value.kind {
+          BindingPatternKind::BindingIdentifier(identifier) => identifier.name,
+          BindingPatternKind::AssignmentPattern(assignment) => match &assignment.left.kind {
+            BindingPatternKind::BindingIdentifier(identifier) => identifier.name,
+            _ => continue,
+          },
+          _ => continue,
+        };
+        if key == local.as_str() {
+          properties.push(local.to_string());
+        } else {
+          properties.push(format!(
+            "{}: {}",
+            serde_json::to_string(key.as_ref()).unwrap_or_default(),
+            local
+          ));
+        }
+      }
+
+      if properties.is_empty() {
+        Some("{}".to_string())
+      } else {
+        Some(format!("{{ {} }}", properties.join(", ")))
+      }
+    }
+    BindingPatternKind::ArrayPattern(_) => None,
+  }
+}
+
+/// Sets the runtime values of a component as custom properties on the elements that use their classes.
+///
+/// Runs before [`crate::update_attribute::ClassNameReplacer`], so the class names are still the
+/// original ones. An existing `style` object is extended, other `style` expressions are spread into
+/// a new object. String styles are left as they are.
+pub struct DynamicStyleBinder<'a, 'v> {
+  pub allocator: &'a Allocator,
+  pub ast_builder: AstBuilder<'a>,
+  pub source_text: &'a str,
+  pub source_type: SourceType,
+  /// Runtime values of the component
+  pub values: &'v [DynamicValue],
+  /// Code of the component props, eg: `props` or `this.props`
+  pub props_code: &'v str,
+  pub class_name_list: Vec<String>,
+  /// The component the values belong to, the function / class span start
+  pub css_id: u32,
+  /// Elements that were already bound, by their span start and `css_id`.
+  /// Nested functions of a component are visited again by their own binder.
+  pub bound_elements: &'v mut HashSet<(u32, u32)>,
+  /// Span starts of the values that were set on at least one element, the others are reported
+  pub bound_values: &'v mut HashSet<u32>,
+  pub patches: Patches,
+}
+
+impl<'a> DynamicStyleBinder<'a, '_> {
+  /// Code of the value of a custom property, eg: `(props => props.size)(props)` or `` `${size}px` ``
+  fn get_value_code(&self, value: &DynamicValue) -> String {
+    let expression = &self.source_text[value.span.start as usize..value.span.end as usize];
+    let code = if value.is_function {
+      format!("({})({})", expression, self.props_code)
+    } else {
+      expression.to_string()
+    };
+
+    if value.unit.is_empty() {
+      code
+    } else {
+      format!("`${{{}}}{}`", code, value.unit)
+    }
+  }
+
+  /// Parses generated code. It doesn't exist in the source, so its spans are cleared.
+  fn parse_expression(&self, code: &str) -> Option<Expression<'a>> {
+    let code = self.allocator.alloc_str(code);
+    let mut expression = Parser::new(self.allocator, code, self.source_type)
+      .parse_expression()
+      .ok()?;
+    SpanEraser.visit_expression(&mut expression);
+    Some(expression)
+  }
+
+  fn bind(&mut self, element: &mut JSXOpeningElement<'a>, values: &[&DynamicValue]) {
+    let properties = values
+      .iter()
+      .map(|value| {
+        format!(
+          "{}: {}",
+          serde_json::to_string(&value.name).unwrap_or_default(),
+          self.get_value_code(value)
+        )
+      })
+      .collect::<Vec<_>>()
+      .join(", ");
+    let object_code = format!("{{ {} }}", properties);
+
+    let style = element.attributes.iter_mut().find_map(|attribute| match attribute {
+      JSXAttributeItem::Attribute(attribute)
+        if matches!(&attribute.name, JSXAttributeName::Identifier(name) if name.name == "style") =>
+      {
+        Some(attribute)
+      }
+      _ => None,
+    });
+
+    let Some(style) = style else {
+      // `<div className="btn">` -> `<div className="btn" style={{ "--flair-...": ... }}>`
+      let Some(object) = self.parse_expression(&object_code) else {
+        return;
+      };
+      self.patches.insert(
+        element.name.span().end,
+        format!(" style={{{}}}", object_code),
+      );
+      element.attributes.push(
+        self.ast_builder.jsx_attribute_item_attribute(
+          SPAN,
+          self
+            .ast_builder
+            .jsx_attribute_name_identifier(SPAN, "style"),
+          Some(
+            self
+              .ast_builder
+              .jsx_attribute_value_expression_container(SPAN, JSXExpression::from(object)),
+          ),
+        ),
+      );
+      return;
+    };
+
+    let Some(JSXAttributeValue::ExpressionContainer(container)) = &mut style.value else {
+      return;
+    };
+    match &mut container.expression {
+      JSXExpression::EmptyExpression(_) => {}
+      // `style={{ color }}` -> `style={{ "--flair-...": ..., color }}`, the written properties win
+      JSXExpression::ObjectExpression(object) => {
+        let Some(Expression::ObjectExpression(mut generated)) = self.parse_expression(&object_code)
+        else {
+          return;
+        };
+        self
+          .patches
+          .insert(object.span.start + 1, format!(" {},", properties));
+        let properties: Vec<ObjectPropertyKind<'a>> = generated.properties.drain(..).collect();
+        for (index, property) in properties.into_iter().enumerate() {
+          object.properties.insert(index, property);
+        }
+      }
+      // `style={styles}` -> `style={{ ...styles, "--flair-...": ... }}`
+      expression => {
+        let span = expression.span();
+        let code = format!(
+          "{{ ...{}, {} }}",
+          &self.source_text[span.start as usize..span.end as usize],
+          properties
+        );
+        let Some(generated) = self.parse_expression(&code) else {
+          return;
+        };
+        self.patches.replace(span, code);
+        *expression = JSXExpression::from(generated);
+      }
+    }
+  }
+}
+
+impl<'a> VisitMut<'a> for DynamicStyleBinder<'a, '_> {
+  fn visit_jsx_opening_element(&mut self, it: &mut JSXOpeningElement<'a>) {
+    let class_names = get_element_class_names(it, &self.class_name_list);
+    let all_values = self.values;
+    let values: Vec<&DynamicValue> = all_values
+      .iter()
+      .filter(|value| {
+        value
+          .class_names
+          .iter()
+          .any(|class_name| class_names.contains(class_name))
+      })
+      .collect();
+
+    self
+      .bound_values
+      .extend(values.iter().map(|value| value.span.start));
+    if !values.is_empty() && self.bound_elements.insert((it.span.start, self.css_id)) {
+      self.bind(it, &values);
+    }
+
+    walk_mut::walk_jsx_opening_element(self, it);
+  }
+}
+
+/// Class names written in the class name attributes of an element, including the strings of
+/// expressions such as `cn("btn", active && "active")`
+fn get_element_class_names(element: &JSXOpeningElement, class_name_list: &[String]) -> Vec<String> {
+  let mut collector = ClassNameCollector::default();
+
+  for attribute in &element.attributes {
+    let JSXAttributeItem::Attribute(attribute) = attribute else {
+      continue;
+    };
+    let JSXAttributeName::Identifier(name) = &attribute.name else {
+      continue;
+    };
+    if !is_class_name_attribute(class_name_list, &name.name) {
+      continue;
+    }
+
+    match &attribute.value {
+      Some(JSXAttributeValue::StringLiteral(string)) => collector.visit_string_literal(string),
+      Some(JSXAttributeValue::ExpressionContainer(container)) => {
+        collector.visit_jsx_expression(&container.expression)
+      }
+      _ => {}
+    }
+  }
+
+  collector.class_names
+}
+
+#[derive(Default)]
+struct ClassNameCollector {
+  class_names: Vec<String>,
+}
+
+impl<'a> Visit<'a> for ClassNameCollector {
+  fn visit_string_literal(&mut self, it: &StringLiteral<'a>) {
+    self
+      .class_names
+      .extend(it.value.split_whitespace().map(str::to_string));
+  }
+
+  fn visit_template_element(&mut self, it: &TemplateElement<'a>) {
+    self
+      .class_names
+      .extend(it.value.raw.split_whitespace().map(str::to_string));
+  }
+}
+
+struct SpanEraser;
+
+impl VisitMut<'_> for SpanEraser {
+  fn visit_span(&mut self, it: &mut Span) {
+    *it = SPAN;
+  }
+}
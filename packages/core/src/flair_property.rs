@@ -13,8 +13,13 @@ use oxc::ast::ast::{AssignmentTarget, Expression};
 use oxc::ast::AstBuilder;
 use oxc::semantic::Scoping;
 use oxc::semantic::SymbolId;
+use oxc::span::Span;
 use std::collections::HashMap;
 
+use crate::css_source_map::CssSourceMapping;
+use crate::dynamic_style::DynamicValues;
+use crate::imports::{is_namespace_member, FlairApi};
+use crate::static_eval::{get_template_css, NonStaticExpression, StaticEvaluator};
 use crate::transform::CSSData;
 
 pub static FLAIR_REPLACEMENT: &str = "__flair_replacement__";
@@ -37,22 +42,39 @@ pub struct FlairProperty<'a> {
   /// Since oxc directly doesn't provide an id for function/arrow expressions,
   /// we use span.start as a unique identifier for the function/arrow.
   symbol_to_span_start_map: HashMap<SymbolId, u32>,
+  /// Name of the component at each span start, the first binding recorded for a span wins.
+  ///
+  /// Eg: `const Card = forwardRef(function CardInner() { ... })` -> `Card`, the variable is
+  /// visited before the function expression in its initializer
+  span_start_to_name: HashMap<u32, String>,
   allocator: &'a Allocator,
   ast_builder: AstBuilder<'a>,
+  /// Names of the style properties and the object helper
+  api: FlairApi,
+  /// Namespace and default imports of flair modules, eg: `F` for `F.flair({ ... })`
+  namespace_symbols: Vec<SymbolId>,
 }
 
 impl<'a> FlairProperty<'a> {
-  pub fn new(scoping: &'a Scoping, allocator: &'a Allocator) -> FlairProperty<'a> {
+  pub fn new(scoping: &'a Scoping, allocator: &'a Allocator, api: FlairApi) -> FlairProperty<'a> {
     FlairProperty {
       scoping,
       style: IndexMap::new(),
       global_style: IndexMap::new(),
       symbol_to_span_start_map: HashMap::new(),
+      span_start_to_name: HashMap::new(),
       allocator,
       ast_builder: AstBuilder::new(allocator),
+      api,
+      namespace_symbols: vec![],
     }
   }
 
+  /// Registers a namespace or default import of a flair module, see [`is_namespace_member`]
+  pub fn add_namespace_symbol(&mut self, symbol_id: SymbolId) {
+    self.namespace_symbols.push(symbol_id);
+  }
+
   pub fn get_scoped_style(&self) -> &IndexMap<u32, CSSData> {
     &self.style
   }
@@ -61,10 +83,43 @@ impl<'a> FlairProperty<'a> {
     &self.global_style
   }
 
+  /// Returns the name of the component whose function / class starts at `span_start`.
+  ///
+  /// Eg: `const Button = () => { ... }` -> `Some("Button")` for the span start of the arrow function
+  pub fn get_component_name(&self, span_start: u32) -> Option<&str> {
+    self.span_start_to_name.get(&span_start).map(String::as_str)
+  }
+
+  /// Maps `symbol_id` to the component starting at `span_start`
+  fn add_component(&mut self, symbol_id: SymbolId, span_start: u32) {
+    self.symbol_to_span_start_map.insert(symbol_id, span_start);
+    self
+      .span_start_to_name
+      .entry(span_start)
+      .or_insert_with(|| self.scoping.symbol_name(symbol_id).to_string());
+  }
+
+  /// Whether `callee` is the object helper, eg: `flair` or `F.flair` for a flair namespace `F`
+  fn is_flair_helper(&self, callee: &Expression) -> bool {
+    match callee {
+      Expression::Identifier(identifier) => self
+        .api
+        .flair_helper
+        .iter()
+        .any(|name| identifier.name == name.as_str()),
+      _ => is_namespace_member(
+        self.scoping,
+        &self.namespace_symbols,
+        callee,
+        &self.api.flair_helper,
+      ),
+    }
+  }
+
   /// Visit variable declarations to find functions assigned to variables
   ///
   /// For example:
-  /// ```
+  /// ```js
   /// const MyComponent = () => { ... }
   /// const MyComponent = function() { ... }
   /// ```
@@ -80,9 +135,7 @@ impl<'a> FlairProperty<'a> {
 
           if let Some(span_start) = item {
             // Map the variable's symbol ID to the function's span start
-            self
-              .symbol_to_span_start_map
-              .insert(ident.symbol_id(), span_start);
+            self.add_component(ident.symbol_id(), span_start);
           }
         }
       }
@@ -96,79 +149,80 @@ impl<'a> FlairProperty<'a> {
       return;
     };
     // Map the class symbol ID to its span start
-    self
-      .symbol_to_span_start_map
-      .insert(class_id.symbol_id(), it.span.start);
+    self.add_component(class_id.symbol_id(), it.span.start);
   }
 
   pub fn visit_function(&mut self, it: &mut Function<'a>) {
-    if let Some(name) = &it.id {
-      self
-        .symbol_to_span_start_map
-        .insert(name.symbol_id(), it.span.start);
+    // Functions generated by the transform have no symbol
+    if let Some(symbol_id) = it.id.as_ref().and_then(|name| name.symbol_id.get()) {
+      self.add_component(symbol_id, it.span.start);
     }
   }
 
   /// Handles assignment expressions like `Component.flair = ...` or `Component.globalFlair = ...`.
   /// Extracts the CSS content from the right-hand side and stores it in the appropriate style map.
   /// Replaces the original assignment with a string literal marker in the AST.
-  pub fn visit_expression(&mut self, it: &mut Expression<'a>) {
+  ///
+  /// Interpolations of template literals are evaluated with `evaluator`. Scoped styles may use
+  /// runtime values, see [`DynamicValues`], `file_id` names their custom properties. When an
+  /// interpolation can't be used, no CSS is stored and the error is returned. The assignment is
+  /// replaced all the same, as the interpolation may refer to the scope of the component.
+  pub fn visit_expression(
+    &mut self,
+    it: &mut Expression<'a>,
+    evaluator: &StaticEvaluator,
+    file_id: &str,
+  ) -> Result<(), NonStaticExpression> {
     // Only process assignment expressions
     let Expression::AssignmentExpression(assign) = it else {
-      return;
+      return Ok(());
     };
 
     // Only handle static member assignments (e.g., Component.flair)
     let AssignmentTarget::StaticMemberExpression(static_member) = &assign.left else {
-      return;
+      return Ok(());
     };
 
     // Only handle assignments to identifiers (not computed properties)
     let Expression::Identifier(ident) = &static_member.object else {
-      return;
+      return Ok(());
     };
 
     // Get the symbol ID for the identifier being assigned to
     let reference = ident.reference_id();
     let Some(symbol_id) = self.scoping.get_reference(reference).symbol_id() else {
-      return;
+      return Ok(());
     };
 
-    // Only process if the symbol is tracked and the property is 'flair' or 'globalFlair'
-    if !self.symbol_to_span_start_map.contains_key(&symbol_id)
-      || !(static_member.property.name.as_str() == "flair"
-        || static_member.property.name.as_str() == "globalFlair")
-    {
-      return;
+    // Only process if the symbol is tracked and the property is 'flair' or 'globalFlair' (or an alias)
+    if !self.symbol_to_span_start_map.contains_key(&symbol_id) {
+      return Ok(());
     }
+    let Some(is_global) = self
+      .api
+      .get_property_kind(static_member.property.name.as_str())
+    else {
+      return Ok(());
+    };
 
+    let span = assign.span;
     let content = &assign.right;
+    let mut dynamic_values = DynamicValues::new(file_id, true);
+    let dynamic = (!is_global).then_some(&mut dynamic_values);
     // Extract CSS content from the right-hand side expression
-    let css_content: String = match content {
-      // Direct string assignment
-      Expression::StringLiteral(string_value) => string_value.value.to_string(),
+    let css: Result<(String, CssSourceMapping), NonStaticExpression> = match content {
+      // Direct string assignment, the CSS starts after the opening quote
+      Expression::StringLiteral(string_value) => Ok((
+        string_value.value.to_string(),
+        CssSourceMapping::verbatim(string_value.span.start + 1),
+      )),
       // Template literal assignment
       Expression::TemplateLiteral(template_expression) => {
-        let template_value = template_expression
-          .quasis
-          .iter()
-          .map(|elem| elem.value.clone().raw.into_string())
-          .collect::<Vec<String>>()
-          .join("");
-
-        template_value
+        get_template_css(template_expression, evaluator, dynamic)
       }
       Expression::TaggedTemplateExpression(tagged_template) => {
         // Handle tagged template literals (e.g., css`body { color: red; }`)
-        let tagged_template_value = tagged_template
-          .quasi
-          .quasis
-          .iter()
-          .map(|elem| elem.value.clone().raw.into_string())
-          .collect::<Vec<String>>()
-          .join("");
-
-        tagged_template_value
+        get_template_css(&tagged_template.quasi, evaluator, dynamic)
       }
       // Assignment via flair({...}) call
       Expression::CallExpression(call_expr) => {
@@ -179,26 +233,35 @@ impl<'a> FlairProperty<'a> {
             .first()
             .and_then(|arg| arg.as_expression()),
         ) {
-          // Only handle flair({...}) calls
-          (Expression::Identifier(identifier_calle), Some(Expression::ObjectExpression(obj)))
-            if identifier_calle.name == "flair" =>
-          {
-            build_style_string_from_object(obj)
+          // Only handle flair({...}) and F.flair({...}) calls, or calls of an alias of the helper
+          // The CSS is generated from the object, so it maps to the start of the object
+          (callee, Some(Expression::ObjectExpression(obj))) if self.is_flair_helper(callee) => {
+            build_style_string_from_object(obj, evaluator)
+              .map(|css| (css, CssSourceMapping::generated(obj.span.start)))
           }
-          _ => String::from(""),
+          _ => Ok((String::from(""), CssSourceMapping::default())),
         }
       }
       // Other types are ignored
-      _ => String::from(""),
+      _ => Ok((String::from(""), CssSourceMapping::default())),
+    };
+    let (css_content, source) = match css {
+      Ok(css) => css,
+      Err(err) => {
+        self.replace_assignment(it, span);
+        return Err(err);
+      }
     };
 
     // Store the CSS content in the appropriate style map
-    if static_member.property.name.as_str() == "globalFlair" {
+    if is_global {
       self.global_style.insert(
         *self.symbol_to_span_start_map.get(&symbol_id).unwrap(),
         CSSData {
           raw_css: css_content,
           is_global: true,
+          source,
+          dynamic_values: vec![],
         },
       );
     } else {
@@ -207,19 +270,27 @@ impl<'a> FlairProperty<'a> {
         CSSData {
           raw_css: css_content,
           is_global: false,
+          source,
+          dynamic_values: dynamic_values.values,
         },
       );
     }
 
-    // Replace the assignment expression in the AST with a string literal marker
-    // This marker will be deleted later in the transformation process
+    self.replace_assignment(it, span);
+
+    Ok(())
+  }
+
+  /// Replaces the assignment expression in the AST with a string literal marker.
+  /// This marker will be deleted later in the transformation process
+  fn replace_assignment(&self, it: &mut Expression<'a>, span: Span) {
     let atom = self
       .ast_builder
       .atom(self.allocator.alloc_str(FLAIR_REPLACEMENT));
 
     *it = Expression::StringLiteral(OxcBox::new_in(
       StringLiteral {
-        span: assign.span,
+        span,
         value: atom,
         raw: None,
         lone_surrogates: false,
@@ -280,7 +351,13 @@ fn camel_case_to_kebab_case(input: &str) -> String {
 }
 
 /// Builds a CSS style string from an object expression (used for flair({...}) calls)
-fn build_style_string_from_object(object_expression: &ObjectExpression) -> String {
+///
+/// Values other than literals are evaluated with `evaluator`, eg: `` padding: `${SPACING * 2}px` ``.
+/// A value that can't be evaluated at build time returns the error.
+fn build_style_string_from_object(
+  object_expression: &ObjectExpression,
+  evaluator: &StaticEvaluator,
+) -> Result<String, NonStaticExpression> {
   let mut style_string = String::new();
 
   for prop in &object_expression.properties {
@@ -307,7 +384,7 @@ fn build_style_string_from_object(object_expression: &ObjectExpression) -> Strin
               (Expression::NumericLiteral(numeric_literal), "-" | "+") => {
                 format!("{}{}", unary_expr.operator.as_str(), numeric_literal.value)
               }
-              _ => "".to_string(),
+              _ => evaluator.evaluate_to_string(&object_property.value)?,
             }
           }
           Expression::BooleanLiteral(boolean_literal) => {
@@ -322,10 +399,13 @@ fn build_style_string_from_object(object_expression: &ObjectExpression) -> Strin
             let object = nested_object.as_ref();
             separator = String::from(" ");
             suffix = String::from("");
-            format!("{{ {} }}", build_style_string_from_object(object))
+            format!(
+              "{{ {} }}",
+              build_style_string_from_object(object, evaluator)?
+            )
           }
-          // Other types are ignored
-          _ => "".to_string(),
+          // Template literals, constants and other expressions that are static
+          value => evaluator.evaluate_to_string(value)?,
         };
 
         // Convert camelCase to kebab-case only for CSS properties (not selectors)
@@ -349,5 +429,5 @@ fn build_style_string_from_object(object_expression: &ObjectExpression) -> Strin
     }
   }
 
-  style_string
+  Ok(style_string)
 }
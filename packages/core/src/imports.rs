@@ -0,0 +1,133 @@
+use oxc::ast::ast::{Expression, JSXMemberExpression, JSXMemberExpressionObject};
+use oxc::semantic::{ReferenceId, Scoping, SymbolId};
+
+use crate::transform::FlairImports;
+
+/// Module the flair APIs are imported from. Subpaths such as `@flairjs/client/react` match as well.
+pub const IMPORT_PATH: &str = "@flairjs/client";
+
+/// Names the flair APIs are recognised by, the defaults plus the aliases of the `imports` option.
+///
+/// Eg: with `imports: { sources: ["@acme/ui/styling"], style: ["Styles"] }`,
+/// `import { Styles } from "@acme/ui/styling"` is handled like `import { Style } from "@flairjs/client"`.
+#[derive(Debug, Clone)]
+pub struct FlairApi {
+  /// Additional module sources, `IMPORT_PATH` is always included
+  sources: Vec<String>,
+  /// Names of the Style component
+  pub style: Vec<String>,
+  /// Names of the class name utilities
+  pub class_name_utils: Vec<String>,
+  /// Names of the object helper, eg: `flair({ ... })`
+  pub flair_helper: Vec<String>,
+  /// Names of the scoped style property, eg: `Component.flair`
+  pub flair_property: Vec<String>,
+  /// Names of the global style property, eg: `Component.globalFlair`
+  pub global_flair_property: Vec<String>,
+}
+
+impl FlairApi {
+  pub fn from_options(options: &Option<FlairImports>) -> Self {
+    let with_defaults = |defaults: &[&str], aliases: Option<&Vec<String>>| {
+      let mut names: Vec<String> = defaults.iter().map(|name| name.to_string()).collect();
+      names.extend(aliases.into_iter().flatten().cloned());
+      names
+    };
+
+    let options = options.as_ref();
+    Self {
+      sources: options
+        .and_then(|imports| imports.sources.clone())
+        .unwrap_or_default(),
+      style: with_defaults(&["Style"], options.and_then(|i| i.style.as_ref())),
+      class_name_utils: with_defaults(
+        &["c", "cn"],
+        options.and_then(|i| i.class_name_utils.as_ref()),
+      ),
+      flair_helper: with_defaults(&["flair"], options.and_then(|i| i.flair_helper.as_ref())),
+      flair_property: with_defaults(&["flair"], options.and_then(|i| i.flair_property.as_ref())),
+      global_flair_property: with_defaults(
+        &["globalFlair"],
+        options.and_then(|i| i.global_flair_property.as_ref()),
+      ),
+    }
+  }
+
+  /// Whether an import from `source` can provide flair APIs.
+  /// Additional sources match exactly or as a path prefix, eg: `@acme/ui` matches `@acme/ui/react`
+  /// but not `@acme/ui-kit`.
+  pub fn is_source(&self, source: &str) -> bool {
+    source.starts_with(IMPORT_PATH)
+      || self.sources.iter().any(|import_source| {
+        source
+          .strip_prefix(import_source.as_str())
+          .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+      })
+  }
+
+  /// Whether `name` is a style property, returning `Some(true)` for the global one
+  pub fn get_property_kind(&self, name: &str) -> Option<bool> {
+    if self
+      .global_flair_property
+      .iter()
+      .any(|property| property == name)
+    {
+      Some(true)
+    } else if self.flair_property.iter().any(|property| property == name) {
+      Some(false)
+    } else {
+      None
+    }
+  }
+}
+
+/// Whether `expression` is a member of a flair namespace named like one of `names`.
+///
+/// Namespaces are the namespace and default imports of the flair modules, eg: `F` in
+/// `import * as F from "@flairjs/client"`, so `F.cn` is a member named `cn`.
+pub fn is_namespace_member(
+  scoping: &Scoping,
+  namespace_symbols: &[SymbolId],
+  expression: &Expression,
+  names: &[String],
+) -> bool {
+  let Expression::StaticMemberExpression(member) = expression else {
+    return false;
+  };
+  let Expression::Identifier(object) = &member.object else {
+    return false;
+  };
+
+  is_namespace_reference(scoping, namespace_symbols, object.reference_id.get())
+    && names
+      .iter()
+      .any(|name| member.property.name == name.as_str())
+}
+
+/// Same as [`is_namespace_member`], for JSX element names such as `<F.Style>`
+pub fn is_jsx_namespace_member(
+  scoping: &Scoping,
+  namespace_symbols: &[SymbolId],
+  member: &JSXMemberExpression,
+  names: &[String],
+) -> bool {
+  let JSXMemberExpressionObject::IdentifierReference(object) = &member.object else {
+    return false;
+  };
+
+  is_namespace_reference(scoping, namespace_symbols, object.reference_id.get())
+    && names
+      .iter()
+      .any(|name| member.property.name == name.as_str())
+}
+
+fn is_namespace_reference(
+  scoping: &Scoping,
+  namespace_symbols: &[SymbolId],
+  reference_id: Option<ReferenceId>,
+) -> bool {
+  // Nodes generated by the transform have no reference
+  reference_id
+    .and_then(|reference_id| scoping.get_reference(reference_id).symbol_id())
+    .is_some_and(|symbol_id| namespace_symbols.contains(&symbol_id))
+}
@@ -1,51 +1,38 @@
 #![deny(clippy::all)]
 
-use std::time::Instant;
-
-use napi::bindgen_prelude::{Env, Function};
-
-use crate::transform::{TransformOptions, TransformOutput};
+//! Flair's CSS-in-JS transform.
+//!
+//! The Rust API is [`transform`] / [`transform_many`] with plain option structs and a
+//! [`CssPreprocessor`] hook. [`transform_program`] runs on an AST that was already parsed with oxc. The Node.js bindings are built on top of it with the `napi` feature (default).
 
+#[cfg(feature = "napi")]
 #[macro_use]
 extern crate napi_derive;
 
+#[cfg(feature = "napi")]
+pub mod bindings;
+pub mod css_output;
+pub mod css_source_map;
+pub mod dynamic_style;
 pub mod flair_property;
+pub mod imports;
 pub mod logger;
+pub mod manifest;
 pub mod parse_css;
+pub mod patch;
+pub mod preprocessor;
+pub mod source_map;
+pub mod static_eval;
 pub mod style_tag;
+pub mod targets;
 pub mod transform;
 pub mod update_attribute;
 
-pub use crate::logger::{log_error, log_info, log_warn};
-
-#[napi]
-pub fn transform_code(
-  env: Env,
-  code: String,
-  file_path: String,
-  options: TransformOptions,
-  css_preprocessor: Option<Function<String, String>>,
-) -> Option<TransformOutput> {
-  let time = Instant::now();
-
-  // Example of using the logging system
-  if cfg!(debug_assertions) {
-    println!("Starting transformation for file: {}", file_path);
-  }
-
-  let options = transform::TransformOptions {
-    css_out_dir: options.css_out_dir,
-    class_name_list: options.class_name_list,
-    use_theme: options.use_theme,
-    append_timestamp_to_css_file: options.append_timestamp_to_css_file,
-    theme: options.theme,
-  };
-  let result = transform::transform(code, file_path, options, css_preprocessor, Some(env));
-  let duration = time.elapsed();
-
-  if cfg!(debug_assertions) {
-    println!("Transformation completed in {:?}", duration);
-  }
-
-  result
-}
+pub use crate::logger::{LogEntry, LogLevel};
+pub use crate::manifest::{prune_css_files, Manifest};
+pub use crate::preprocessor::CssPreprocessor;
+pub use crate::transform::{
+  transform, transform_many, transform_program, ComponentMetadata, CssImport, CssModulesOptions,
+  CssTargets, FlairImports, ProgramTransformOutput, Theme, TransformFile, TransformOptions,
+  TransformOutput,
+};
@@ -1,9 +1,15 @@
-use napi_derive::napi;
-use once_cell::sync::Lazy;
-use std::sync::{Arc, Mutex};
+use cssparser::SourceLocation;
+use oxc::diagnostics::OxcDiagnostic;
+use std::fmt;
+use std::sync::Mutex;
+
+use crate::css_source_map::{CssSourceMapping, LineIndex};
+
+/// Lines shown before and after the highlighted line in a code frame
+const CODE_FRAME_CONTEXT_LINES: usize = 2;
 
 /// Log level enum that matches your required structure
-#[napi]
+#[cfg_attr(feature = "napi", napi)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum LogLevel {
   Error,
@@ -11,13 +17,96 @@ pub enum LogLevel {
   Info,
 }
 
+/// Stable codes that identify the kind of a log entry, so tools can filter, group or link to them.
+/// The string form returned by [`DiagnosticCode::as_str`] is what ends up in `LogEntry.code`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiagnosticCode {
+  /// The file could not be parsed as JavaScript / TypeScript
+  JsParseError,
+  /// The file extension doesn't map to a known source type
+  UnsupportedSourceType,
+  /// The `css_preprocessor` callback failed, the original CSS was used
+  CssPreprocessorError,
+  /// lightningcss could not parse the styles of a component
+  CssParseError,
+  /// lightningcss could not print the styles of a component, eg: an undefined CSS modules reference
+  CssPrintError,
+  /// The CSS modules `pattern` option is invalid
+  InvalidCssModulesPattern,
+  /// A `$theme.token` doesn't follow the expected format
+  InvalidThemeToken,
+  /// `@screen` refers to a breakpoint that isn't in the theme
+  UnknownBreakpoint,
+  /// A browserslist query in the `targets` option is invalid
+  InvalidBrowserslist,
+  /// A feature name in `targets.include` / `targets.exclude` is unknown
+  UnknownCssFeature,
+  /// `input_sourcemap` could not be composed with the generated source map
+  InvalidInputSourcemap,
+  /// The source map of the generated CSS could not be built
+  CssSourcemapError,
+  /// `css_out_dir` is missing while `css_output` is `file`
+  MissingCssOutDir,
+  /// The generated CSS could not be written to disk
+  CssWriteError,
+  /// The system clock is before the UNIX epoch, so no timestamp could be appended
+  InvalidTimestamp,
+  /// The manifest in `css_out_dir` could not be read or written
+  ManifestError,
+  /// An interpolation in the CSS of a template literal can't be evaluated at build time
+  NonStaticInterpolation,
+  /// A runtime value was not set on any element, as none uses the classes of its rule
+  UnboundRuntimeValue,
+}
+
+impl DiagnosticCode {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      DiagnosticCode::JsParseError => "js-parse-error",
+      DiagnosticCode::UnsupportedSourceType => "unsupported-source-type",
+      DiagnosticCode::CssPreprocessorError => "css-preprocessor-error",
+      DiagnosticCode::CssParseError => "css-parse-error",
+      DiagnosticCode::CssPrintError => "css-print-error",
+      DiagnosticCode::InvalidCssModulesPattern => "invalid-css-modules-pattern",
+      DiagnosticCode::InvalidThemeToken => "invalid-theme-token",
+      DiagnosticCode::UnknownBreakpoint => "unknown-breakpoint",
+      DiagnosticCode::InvalidBrowserslist => "invalid-browserslist",
+      DiagnosticCode::UnknownCssFeature => "unknown-css-feature",
+      DiagnosticCode::InvalidInputSourcemap => "invalid-input-sourcemap",
+      DiagnosticCode::CssSourcemapError => "css-sourcemap-error",
+      DiagnosticCode::MissingCssOutDir => "missing-css-out-dir",
+      DiagnosticCode::CssWriteError => "css-write-error",
+      DiagnosticCode::InvalidTimestamp => "invalid-timestamp",
+      DiagnosticCode::ManifestError => "manifest-error",
+      DiagnosticCode::NonStaticInterpolation => "non-static-interpolation",
+      DiagnosticCode::UnboundRuntimeValue => "unbound-runtime-value",
+    }
+  }
+}
+
 /// Log entry structure that will be sent to the JS side
-#[napi(object)]
+#[cfg_attr(feature = "napi", napi(object))]
 #[derive(Debug, Clone)]
 pub struct LogEntry {
   pub message: String,
-  #[napi(ts_type = " 'error' | 'warn' | 'info' ")]
+  #[cfg_attr(feature = "napi", napi(ts_type = " 'error' | 'warn' | 'info' "))]
   pub level: String, // "error", "warn", or "info" for JS compatibility
+  /// Stable code of the problem, eg: `css-parse-error`
+  pub code: Option<String>,
+  /// Path of the file the entry refers to
+  pub file: Option<String>,
+  /// 1-based line of the location the entry refers to
+  pub line: Option<u32>,
+  /// 1-based column of the location the entry refers to
+  pub column: Option<u32>,
+  /// 1-based line where the location ends
+  pub end_line: Option<u32>,
+  /// 1-based column where the location ends (exclusive)
+  pub end_column: Option<u32>,
+  /// Suggestion on how to fix the problem
+  pub help: Option<String>,
+  /// Source lines around the location, with the location highlighted
+  pub frame: Option<String>,
 }
 
 impl LogEntry {
@@ -31,31 +120,290 @@ impl LogEntry {
     Self {
       message,
       level: level_str,
+      code: None,
+      file: None,
+      line: None,
+      column: None,
+      end_line: None,
+      end_column: None,
+      help: None,
+      frame: None,
+    }
+  }
+
+  /// Creates an entry with a stable code
+  pub fn diagnostic(code: DiagnosticCode, level: LogLevel, message: impl Into<String>) -> Self {
+    let mut entry = Self::new(message.into(), level);
+    entry.code = Some(code.as_str().to_string());
+    entry
+  }
+
+  pub fn with_file(mut self, file: &str) -> Self {
+    self.file = Some(file.to_string());
+    self
+  }
+
+  pub fn with_help(mut self, help: impl Into<String>) -> Self {
+    self.help = Some(help.into());
+    self
+  }
+
+  /// Points the entry at the bytes `start..end` of `source`, and renders the code frame
+  pub fn with_span(
+    mut self,
+    source: &str,
+    source_index: &LineIndex,
+    start: usize,
+    end: usize,
+  ) -> Self {
+    let end = end.max(start);
+    let (line, column) = source_index.position(start);
+    let (end_line, end_column) = source_index.position(end);
+
+    self.line = Some(line + 1);
+    self.column = Some(column + 1);
+    self.end_line = Some(end_line + 1);
+    self.end_column = Some(end_column + 1);
+    self.frame = Some(code_frame(source, start, end - start));
+    self
+  }
+
+  /// Converts an oxc diagnostic (eg: a parse error) into an entry pointing at its primary label.
+  pub fn from_diagnostic(
+    diagnostic: &OxcDiagnostic,
+    code: DiagnosticCode,
+    file: &str,
+    source: &str,
+    source_index: &LineIndex,
+  ) -> Self {
+    let mut entry =
+      Self::diagnostic(code, LogLevel::Error, diagnostic.message.to_string()).with_file(file);
+
+    let label = diagnostic.labels.as_ref().and_then(|labels| {
+      labels
+        .iter()
+        .find(|label| label.primary())
+        .or_else(|| labels.first())
+    });
+
+    if let Some(label) = label {
+      entry = entry.with_span(
+        source,
+        source_index,
+        label.offset(),
+        label.offset() + label.len(),
+      );
+
+      if let Some(text) = label.label() {
+        entry.message = format!("{}: {}", entry.message, text);
+      }
+    }
+
+    if let Some(help) = &diagnostic.help {
+      entry = entry.with_help(help.to_string());
+    }
+
+    entry
+  }
+
+  /// Converts a problem found in an extracted CSS string into an entry pointing into the source file.
+  /// Problems without a location point at the start of the CSS.
+  pub fn from_css_diagnostic(
+    diagnostic: CssDiagnostic,
+    css: &str,
+    mapping: &CssSourceMapping,
+    file: &str,
+    source: &str,
+    source_index: &LineIndex,
+  ) -> Self {
+    let css_offset = diagnostic
+      .location
+      .map(|location| {
+        // cssparser columns start at 1
+        LineIndex::new(css).offset(location.line, location.column.saturating_sub(1))
+      })
+      .unwrap_or(0);
+
+    let mut entry =
+      Self::diagnostic(diagnostic.code, diagnostic.level, diagnostic.message).with_file(file);
+    if let Some(source_offset) = mapping.source_offset(css_offset) {
+      let source_offset = source_offset as usize;
+      entry = entry.with_span(source, source_index, source_offset, source_offset);
+    }
+    if let Some(help) = diagnostic.help {
+      entry = entry.with_help(help);
+    }
+
+    entry
+  }
+}
+
+/// Formats the entry as `file:line:column message [code]`, followed by the help text and code frame
+impl fmt::Display for LogEntry {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let Some(file) = &self.file {
+      write!(f, "{}", file)?;
+      if let Some(line) = self.line {
+        write!(f, ":{}:{}", line, self.column.unwrap_or(1))?;
+      }
+      write!(f, " ")?;
+    }
+    write!(f, "{}", self.message)?;
+    if let Some(code) = &self.code {
+      write!(f, " [{}]", code)?;
+    }
+    if let Some(help) = &self.help {
+      write!(f, "\nHelp: {}", help)?;
+    }
+    if let Some(frame) = &self.frame {
+      write!(f, "\n{}", frame)?;
+    }
+    Ok(())
+  }
+}
+
+/// A problem found in an extracted CSS string.
+/// The location is relative to the CSS, see [`LogEntry::from_css_diagnostic`] to map it to the source file.
+#[derive(Debug, Clone)]
+pub struct CssDiagnostic {
+  pub code: DiagnosticCode,
+  pub level: LogLevel,
+  pub message: String,
+  pub help: Option<String>,
+  /// 0-based line and 1-based column in the CSS, as reported by cssparser and lightningcss
+  pub location: Option<SourceLocation>,
+}
+
+impl CssDiagnostic {
+  pub fn error(code: DiagnosticCode, message: impl Into<String>) -> Self {
+    Self {
+      code,
+      level: LogLevel::Error,
+      message: message.into(),
+      help: None,
+      location: None,
+    }
+  }
+
+  pub fn at(mut self, location: Option<SourceLocation>) -> Self {
+    self.location = location;
+    self
+  }
+
+  pub fn with_help(mut self, help: impl Into<String>) -> Self {
+    self.help = Some(help.into());
+    self
+  }
+}
+
+/// Renders the lines around `offset` with the `len` bytes after it underlined.
+///
+/// ```text
+///   1 | const Button = () => {
+/// > 2 |   return <div className="button">
+///     |          ^^^^
+///   3 | }
+/// ```
+pub fn code_frame(source: &str, offset: usize, len: usize) -> String {
+  let offset = offset.min(source.len());
+  let lines: Vec<&str> = source.split('\n').collect();
+  let line = source[..offset].matches('\n').count();
+  let line_start = source[..offset].rfind('\n').map_or(0, |index| index + 1);
+
+  let first_line = line.saturating_sub(CODE_FRAME_CONTEXT_LINES);
+  let last_line = (line + CODE_FRAME_CONTEXT_LINES).min(lines.len() - 1);
+  let gutter_width = (last_line + 1).to_string().len();
+
+  // Underline up to the end of the line, at least one character
+  let column = source[line_start..offset].chars().count();
+  let end = (offset + len).min(source.len());
+  let marker_len = source[offset..end]
+    .split('\n')
+    .next()
+    .map_or(0, |text| text.chars().count())
+    .max(1);
+
+  let mut frame = vec![];
+  for (index, text) in lines
+    .iter()
+    .enumerate()
+    .take(last_line + 1)
+    .skip(first_line)
+  {
+    let text = text.trim_end_matches('\r');
+    if index == line {
+      frame.push(format!(
+        "> {:>width$} | {}",
+        index + 1,
+        text,
+        width = gutter_width
+      ));
+      frame.push(format!(
+        "  {:>width$} | {}{}",
+        "",
+        " ".repeat(column),
+        "^".repeat(marker_len),
+        width = gutter_width
+      ));
+    } else {
+      frame.push(format!(
+        "  {:>width$} | {}",
+        index + 1,
+        text,
+        width = gutter_width
+      ));
     }
   }
+
+  frame.join("\n")
 }
 
-/// Thread-safe global logger that collects logs from anywhere in the codebase
+/// Collects the logs of a single transform.
+/// Every transform creates its own logger and passes it down, so concurrent transforms never see each other's logs.
 #[derive(Debug)]
 pub struct Logger {
-  logs: Arc<Mutex<Vec<LogEntry>>>,
+  logs: Mutex<Vec<LogEntry>>,
 }
 
 impl Logger {
   pub fn new() -> Self {
     Self {
-      logs: Arc::new(Mutex::new(Vec::new())),
+      logs: Mutex::new(Vec::new()),
     }
   }
 
   /// Add a log entry to the collection
   pub fn log(&self, message: String, level: LogLevel) {
-    let entry = LogEntry::new(message, level);
+    self.log_entry(LogEntry::new(message, level));
+  }
+
+  /// Add a log entry that was built with a location
+  pub fn log_entry(&self, entry: LogEntry) {
     if let Ok(mut logs) = self.logs.lock() {
       logs.push(entry);
     }
   }
 
+  /// Log an error
+  pub fn error(&self, code: DiagnosticCode, message: impl Into<String>) {
+    self.log_entry(LogEntry::diagnostic(code, LogLevel::Error, message));
+  }
+
+  /// Log a warning
+  pub fn warn(&self, code: DiagnosticCode, message: impl Into<String>) {
+    self.log_entry(LogEntry::diagnostic(code, LogLevel::Warn, message));
+  }
+
+  /// Log info
+  pub fn info(&self, message: impl Into<String>) {
+    self.log(message.into(), LogLevel::Info);
+  }
+
+  /// Whether nothing was logged yet
+  pub fn is_empty(&self) -> bool {
+    self.logs.lock().map(|logs| logs.is_empty()).unwrap_or(true)
+  }
+
   /// Get all collected logs and clear the collection
   pub fn drain_logs(&self) -> Vec<LogEntry> {
     if let Ok(mut logs) = self.logs.lock() {
@@ -88,47 +436,26 @@ impl Default for Logger {
   }
 }
 
-/// Global logger instance using once_cell for thread-safe lazy initialization
-static GLOBAL_LOGGER: Lazy<Logger> = Lazy::new(Logger::new);
-
-/// Get a reference to the global logger
-pub fn get_logger() -> &'static Logger {
-  &GLOBAL_LOGGER
-}
-
-/// Convenience function to log an error
-pub fn log_error(message: impl Into<String>) {
-  get_logger().log(message.into(), LogLevel::Error);
-}
-
-/// Convenience function to log a warning
-pub fn log_warn(message: impl Into<String>) {
-  get_logger().log(message.into(), LogLevel::Warn);
-}
-
-/// Convenience function to log info
-pub fn log_info(message: impl Into<String>) {
-  get_logger().log(message.into(), LogLevel::Info);
-}
-
-/// Convenience macros for easy logging throughout the codebase
+/// Convenience macros for easy logging throughout the codebase.
+/// They take the [`Logger`] of the current transform, then a [`DiagnosticCode`] for errors and warnings,
+/// eg: `log_warn!(logger, DiagnosticCode::UnknownCssFeature, "...", name)`
 #[macro_export]
 macro_rules! log_error {
-  ($($arg:tt)*) => {
-    $crate::logger::log_error(format!($($arg)*))
+  ($logger:expr, $code:expr, $($arg:tt)*) => {
+    $logger.error($code, format!($($arg)*))
   };
 }
 
 #[macro_export]
 macro_rules! log_warn {
-  ($($arg:tt)*) => {
-    $crate::logger::log_warn(format!($($arg)*))
+  ($logger:expr, $code:expr, $($arg:tt)*) => {
+    $logger.warn($code, format!($($arg)*))
   };
 }
 
 #[macro_export]
 macro_rules! log_info {
-  ($($arg:tt)*) => {
-    $crate::logger::log_info(format!($($arg)*))
+  ($logger:expr, $($arg:tt)*) => {
+    $logger.info(format!($($arg)*))
   };
 }
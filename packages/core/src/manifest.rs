@@ -0,0 +1,343 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use serde_json::{json, Value};
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::css_output::{resolve_project_relative_path, write_file_atomic};
+
+/// Name of the manifest directory in `css_out_dir`
+pub const MANIFEST_DIR_NAME: &str = "flair-manifest";
+
+/// Bumped when the manifest format changes in an incompatible way
+const MANIFEST_VERSION: u64 = 1;
+
+/// Build-wide record of the CSS generated for every source file, stored in the `flair-manifest`
+/// directory of `css_out_dir`. Every source file has its own entry file, at its project-relative
+/// path with a `.json` extension, so a transform only writes its own entry and parallel builds
+/// never overwrite each other's entries.
+///
+/// `flair-manifest/src/Button.tsx.json`:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "cssFile": "5f1c2e9a0b3d4c6e.css",
+///   "previousCssFiles": ["0b3d4c6e5f1c2e9a.css"],
+///   "contentHash": "9a0b3d4c6e5f1c2e",
+///   "components": [{ "name": "Button", "classNames": { "button": "a1b2c3_button" } }]
+/// }
+/// ```
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Manifest {
+  /// Entries by source path, relative to the project root
+  pub files: BTreeMap<String, ManifestEntry>,
+}
+
+/// CSS generated for a source file
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+  /// Path of the CSS file, relative to `css_out_dir`.
+  /// `None` once the source has no styles, the entry is kept until its earlier files are pruned.
+  pub css_file: Option<String>,
+  /// CSS files generated for the source by earlier builds, that weren't pruned yet
+  pub previous_css_files: Vec<String>,
+  /// xxh3 hash of the CSS
+  pub content_hash: String,
+  pub components: Vec<ManifestComponent>,
+}
+
+/// A component with styles
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestComponent {
+  pub name: Option<String>,
+  /// Original to scoped class names
+  pub class_names: BTreeMap<String, String>,
+}
+
+impl ManifestEntry {
+  pub fn new(css_file: String, css: &str, components: Vec<ManifestComponent>) -> Self {
+    Self {
+      css_file: Some(css_file),
+      previous_css_files: vec![],
+      content_hash: format!("{:016x}", xxh3_64(css.as_bytes())),
+      components,
+    }
+  }
+
+  /// CSS files of the entry, current and previous
+  fn css_files(&self) -> impl Iterator<Item = &String> {
+    self.css_file.iter().chain(&self.previous_css_files)
+  }
+
+  fn to_json(&self) -> Value {
+    let components: Vec<Value> = self
+      .components
+      .iter()
+      .map(|component| {
+        json!({
+          "name": component.name,
+          "classNames": component.class_names,
+        })
+      })
+      .collect();
+
+    json!({
+      "version": MANIFEST_VERSION,
+      "cssFile": self.css_file,
+      "previousCssFiles": self.previous_css_files,
+      "contentHash": self.content_hash,
+      "components": components,
+    })
+  }
+
+  fn from_json(json: &Value) -> Option<Self> {
+    if json.get("version")?.as_u64()? != MANIFEST_VERSION {
+      return None;
+    }
+
+    let mut components = vec![];
+    for component in json.get("components")?.as_array()? {
+      let class_names = component
+        .get("classNames")?
+        .as_object()?
+        .iter()
+        .map(|(class_name, scoped)| Some((class_name.clone(), scoped.as_str()?.to_string())))
+        .collect::<Option<BTreeMap<String, String>>>()?;
+
+      components.push(ManifestComponent {
+        name: component
+          .get("name")
+          .and_then(Value::as_str)
+          .map(str::to_string),
+        class_names,
+      });
+    }
+
+    let previous_css_files = match json.get("previousCssFiles") {
+      Some(files) => files
+        .as_array()?
+        .iter()
+        .map(|file| Some(file.as_str()?.to_string()))
+        .collect::<Option<Vec<String>>>()?,
+      None => vec![],
+    };
+
+    Some(Self {
+      css_file: match json.get("cssFile")? {
+        Value::Null => None,
+        css_file => Some(css_file.as_str()?.to_string()),
+      },
+      previous_css_files,
+      content_hash: json.get("contentHash")?.as_str()?.to_string(),
+      components,
+    })
+  }
+}
+
+impl Manifest {
+  /// Reads the manifest of `css_out_dir`. A missing manifest is empty.
+  pub fn read(css_out_dir: &str) -> Result<Self, String> {
+    let dir = Path::new(css_out_dir).join(MANIFEST_DIR_NAME);
+    let mut entry_files = vec![];
+    if dir.is_dir() {
+      collect_files(&dir, "", ".json", &mut entry_files)?;
+    }
+
+    let mut files = BTreeMap::new();
+    for entry_file in entry_files {
+      let path = dir.join(&entry_file);
+      let content = fs::read_to_string(&path).map_err(|err| {
+        format!(
+          "Failed to read manifest entry: {}, reason: {}",
+          path.display(),
+          err
+        )
+      })?;
+      let json: Value = serde_json::from_str(&content).map_err(|err| {
+        format!(
+          "Failed to parse manifest entry: {}, reason: {}",
+          path.display(),
+          err
+        )
+      })?;
+      let entry = ManifestEntry::from_json(&json)
+        .ok_or_else(|| format!("Invalid manifest entry: {}", path.display()))?;
+
+      let source = entry_file.strip_suffix(".json").unwrap_or(&entry_file);
+      files.insert(source.to_string(), entry);
+    }
+
+    Ok(Self { files })
+  }
+}
+
+/// Writes the entry of `source` to the manifest of `css_out_dir`, `None` when the source has no styles.
+///
+/// The CSS files of the existing entry are kept in `previous_css_files`, so [`prune_css_files`] can
+/// remove them. An entry without any CSS files is removed.
+/// Unchanged entries are not rewritten, see [`write_file_atomic`].
+pub fn update_manifest(
+  css_out_dir: &str,
+  source: &str,
+  entry: Option<ManifestEntry>,
+) -> Result<(), String> {
+  let previous = read_manifest_entry(css_out_dir, source);
+  let mut entry = entry.unwrap_or(ManifestEntry {
+    css_file: None,
+    previous_css_files: vec![],
+    content_hash: String::new(),
+    components: vec![],
+  });
+
+  for css_file in previous.iter().flat_map(ManifestEntry::css_files) {
+    if entry.css_files().all(|file| file != css_file) {
+      entry.previous_css_files.push(css_file.clone());
+    }
+  }
+  write_manifest_entry(css_out_dir, source, &entry)
+}
+
+/// Reads the entry of `source`, `None` when it is missing or invalid
+fn read_manifest_entry(css_out_dir: &str, source: &str) -> Option<ManifestEntry> {
+  let content = fs::read_to_string(get_entry_path(css_out_dir, source)).ok()?;
+  ManifestEntry::from_json(&serde_json::from_str(&content).ok()?)
+}
+
+/// Writes the entry of `source`, or removes it when it has no CSS files
+fn write_manifest_entry(
+  css_out_dir: &str,
+  source: &str,
+  entry: &ManifestEntry,
+) -> Result<(), String> {
+  if entry.css_files().next().is_none() {
+    return remove_manifest_entry(css_out_dir, source);
+  }
+  write_file_atomic(
+    &get_entry_path(css_out_dir, source),
+    &entry.to_json().to_string(),
+  )
+}
+
+/// Path of the entry file of `source`. Project-relative paths never leave the project, see
+/// [`crate::css_output::get_project_relative_path`], so entries stay in the manifest directory.
+fn get_entry_path(css_out_dir: &str, source: &str) -> std::path::PathBuf {
+  Path::new(css_out_dir)
+    .join(MANIFEST_DIR_NAME)
+    .join(format!("{}.json", source))
+}
+
+/// Removes the entry file of `source`, and the directories it leaves empty
+fn remove_manifest_entry(css_out_dir: &str, source: &str) -> Result<(), String> {
+  let path = get_entry_path(css_out_dir, source);
+  match fs::remove_file(&path) {
+    Ok(()) => {}
+    Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+    Err(err) => {
+      return Err(format!(
+        "Failed to remove manifest entry: {}, reason: {}",
+        path.display(),
+        err
+      ))
+    }
+  }
+
+  // Stops at the first directory that isn't empty, the manifest directory itself is kept
+  let manifest_dir = Path::new(css_out_dir).join(MANIFEST_DIR_NAME);
+  let mut dir = path.parent();
+  while let Some(current) = dir {
+    if current == manifest_dir || fs::remove_dir(current).is_err() {
+      break;
+    }
+    dir = current.parent();
+  }
+
+  Ok(())
+}
+
+/// Removes the generated CSS files that are no longer used.
+///
+/// Only files recorded in the manifest are removed: the CSS files of entries whose source file
+/// no longer exists under `root` (the current working directory if not set), and the previous
+/// CSS files of every entry. A file that is still the current CSS file of an entry is kept, as
+/// sources with the same CSS share a `[contenthash]` file. Other files in `css_out_dir` are
+/// never touched. The `.css.map` file of a removed CSS file is removed with it.
+///
+/// Returns the deleted files, relative to `css_out_dir` and sorted.
+/// Nothing is deleted when `css_out_dir` has no manifest.
+/// Source files outside of `root` are found as well, see [`resolve_project_relative_path`].
+pub fn prune_css_files(css_out_dir: &str, root: Option<&str>) -> Result<Vec<String>, String> {
+  let manifest = Manifest::read(css_out_dir)?;
+
+  let mut stale_files = BTreeSet::new();
+  let mut used_files = BTreeSet::new();
+  for (source, entry) in &manifest.files {
+    if resolve_project_relative_path(source, root).is_file() {
+      stale_files.extend(entry.previous_css_files.iter().cloned());
+      used_files.extend(entry.css_file.iter().cloned());
+    } else {
+      stale_files.extend(entry.css_files().cloned());
+    }
+  }
+
+  let mut removed = vec![];
+  for css_file in stale_files.difference(&used_files) {
+    for file in [css_file.clone(), format!("{}.map", css_file)] {
+      let path = Path::new(css_out_dir).join(&file);
+      match fs::remove_file(&path) {
+        Ok(()) => removed.push(file),
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => {
+          return Err(format!(
+            "Failed to remove: {}, reason: {}",
+            path.display(),
+            err
+          ))
+        }
+      }
+    }
+  }
+
+  // The removed files no longer need to be recorded
+  for (source, mut entry) in manifest.files {
+    if resolve_project_relative_path(&source, root).is_file() {
+      entry.previous_css_files.clear();
+      write_manifest_entry(css_out_dir, &source, &entry)?;
+    } else {
+      remove_manifest_entry(css_out_dir, &source)?;
+    }
+  }
+
+  removed.sort();
+  Ok(removed)
+}
+
+/// Collects the files under `dir` whose name ends with `extension`, as paths relative to the walked root
+fn collect_files(
+  dir: &Path,
+  prefix: &str,
+  extension: &str,
+  files: &mut Vec<String>,
+) -> Result<(), String> {
+  let entries = fs::read_dir(dir)
+    .map_err(|err| format!("Failed to read: {}, reason: {}", dir.display(), err))?;
+
+  for entry in entries.flatten() {
+    let name = entry.file_name().to_string_lossy().to_string();
+    let relative = format!("{}{}", prefix, name);
+    let path = entry.path();
+
+    if path.is_dir() {
+      collect_files(&path, &format!("{}/", relative), extension, files)?;
+    } else if name.ends_with(extension) {
+      files.push(relative);
+    }
+  }
+
+  Ok(())
+}
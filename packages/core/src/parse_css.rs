@@ -2,13 +2,37 @@ use std::{collections::HashMap, convert::Infallible};
 
 use cssparser::{ParseError, Parser, ParserInput, SourceLocation, ToCss, Token};
 use lightningcss::{
-  css_modules::{self},
+  css_modules::{self, Pattern},
   printer::PrinterOptions,
   stylesheet::{ParserOptions, StyleSheet, ToCssResult},
-  targets::{Browsers, Features, Targets},
+  targets::Targets,
 };
+use parcel_sourcemap::SourceMap;
 
-use crate::{log_error, transform::Theme};
+use crate::{
+  logger::{CssDiagnostic, DiagnosticCode},
+  transform::{CssModulesOptions, Theme},
+};
+
+/// Options shared by every `parse_css` call of a transform
+pub struct ParseCssOptions<'a> {
+  /// Whether to process theme tokens (e.g., $theme.color.primary -> var(--theme-color-primary))
+  pub use_theme: bool,
+  pub theme: &'a Option<Theme>,
+  /// Class name pattern and scoping options, used for CSS modules
+  pub css_modules: &'a Option<CssModulesOptions>,
+  /// Browser targets and features to compile the CSS for
+  pub targets: Targets,
+  /// Whether to minify the output. Off by default, the users' bundler is expected to handle it
+  pub minify: bool,
+}
+
+/// Result of [`parse_css`]
+pub struct ParsedCss {
+  pub result: ToCssResult,
+  /// Theme tokens used by the CSS, without the `$`, eg: `colors.primary`. Empty unless `use_theme` is set.
+  pub theme_tokens: Vec<String>,
+}
 
 /// Parses CSS string and applies transformations based on configuration flags
 ///
@@ -16,24 +40,28 @@ use crate::{log_error, transform::Theme};
 /// * `css` - Raw CSS string to parse
 /// * `filename` - Name of the file being parsed (used for error reporting and source maps)
 /// * `module` - Whether to enable CSS modules (scoped class names)
-/// * `use_theme` - Whether to process theme tokens (e.g., $theme.color.primary -> var(--theme-color-primary))
+/// * `options` - Theme, CSS modules, targets and minification options
+/// * `source_map` - Receives the mappings from the output to `css`, if provided
+/// * `diagnostics` - Receives the problems that don't stop the CSS from being processed, eg: invalid theme tokens
 ///
 /// # Returns
-/// * `Ok(ToCssResult)` - Parsed and transformed CSS with optional exports (for CSS modules)
-/// * `Err(String)` - Error message if parsing or transformation fails
+/// * `Ok(ParsedCss)` - Parsed and transformed CSS with optional exports (for CSS modules) and the theme tokens it uses
+/// * `Err(CssDiagnostic)` - The problem that stopped the CSS from being parsed or printed
 pub fn parse_css(
   css: &str,
   filename: &str,
   module: bool,
-  use_theme: bool,
-  theme: &Option<Theme>,
-) -> Result<ToCssResult, String> {
+  options: &ParseCssOptions,
+  source_map: Option<&mut SourceMap>,
+  diagnostics: &mut Vec<CssDiagnostic>,
+) -> Result<ParsedCss, CssDiagnostic> {
   // Pre-process CSS to replace theme tokens if enabled
   // Theme tokens like $theme.color.primary get converted to var(--theme-color-primary)
-  let processed_css = if use_theme {
+  let mut theme_tokens = vec![];
+  let processed_css = if options.use_theme {
     let mut input = ParserInput::new(css);
     let mut parser = Parser::new(&mut input);
-    replace_theme_tokens(&mut parser, theme)
+    replace_theme_tokens(&mut parser, options.theme, diagnostics, &mut theme_tokens)
   } else {
     css.to_string()
   };
@@ -43,42 +71,89 @@ pub fn parse_css(
     filename: filename.to_string(),
     // Enable CSS modules if requested - this will scope class names and generate export mappings
     css_modules: if module {
-      Some(css_modules::Config {
-        ..Default::default()
-      })
+      Some(get_css_modules_config(options.css_modules)?)
     } else {
       None
     },
     ..Default::default()
   };
 
-  // Set up browser targets for CSS transformations
-  // "defaults" refers to browserslist's default query (last 2 versions, >0.2% usage, not dead)
-  let browsers = Browsers::from_browserslist(vec!["defaults"]).unwrap_or(None);
-  let targets = Targets {
-    browsers,
-    // Enable CSS nesting support in addition to default features
-    // This allows nested selectors to be processed and flattened if needed for older browsers
-    include: Features::default() | Features::Nesting | Features::MediaRangeSyntax,
-    ..Targets::default()
-  };
-
-  let stylesheet = StyleSheet::parse(&processed_css, parser_options)
-    .map_err(|e| format!("Failed to parse CSS: {}", e))?;
+  // Locations are relative to `processed_css`, which only differs from `css` where theme tokens were replaced
+  let stylesheet = StyleSheet::parse(&processed_css, parser_options).map_err(|e| {
+    CssDiagnostic::error(
+      DiagnosticCode::CssParseError,
+      format!("Failed to parse CSS: {}", e.kind),
+    )
+    .at(e.loc.map(|loc| SourceLocation {
+      line: loc.line,
+      column: loc.column,
+    }))
+  })?;
 
   // Convert the stylesheet back to CSS string with transformations applied
   let result = stylesheet.to_css(PrinterOptions {
-    minify: false, // Expect the users' bundler to handle minification
-    targets,
+    minify: options.minify,
+    targets: options.targets,
+    source_map,
     ..Default::default()
   });
 
   // Handle the conversion result and provide descriptive error messages
   let ret_value = match result {
     Ok(result) => result,
-    Err(e) => return Err(format!("Failed to convert stylesheet to CSS: {}", e)),
+    Err(e) => {
+      return Err(
+        CssDiagnostic::error(
+          DiagnosticCode::CssPrintError,
+          format!("Failed to convert stylesheet to CSS: {}", e.kind),
+        )
+        .at(e.loc.map(|loc| SourceLocation {
+          line: loc.line,
+          column: loc.column,
+        })),
+      )
+    }
   };
-  Ok(ret_value)
+  Ok(ParsedCss {
+    result: ret_value,
+    theme_tokens,
+  })
+}
+
+/// Builds the lightningcss CSS modules config from the user options.
+/// Options that are not set keep the lightningcss defaults.
+fn get_css_modules_config(
+  options: &Option<CssModulesOptions>,
+) -> Result<css_modules::Config, CssDiagnostic> {
+  let default_config = css_modules::Config::default();
+  let Some(options) = options else {
+    return Ok(default_config);
+  };
+
+  let pattern = match &options.pattern {
+    Some(pattern) => Pattern::parse(pattern).map_err(|e| {
+      CssDiagnostic::error(
+        DiagnosticCode::InvalidCssModulesPattern,
+        format!("Invalid CSS modules pattern '{}': {}", pattern, e),
+      )
+      .with_help("Patterns can use the [hash], [local], [name] and [content-hash] placeholders")
+    })?,
+    None => default_config.pattern,
+  };
+
+  Ok(css_modules::Config {
+    pattern,
+    animation: options.animation.unwrap_or(default_config.animation),
+    grid: options.grid.unwrap_or(default_config.grid),
+    custom_idents: options
+      .custom_idents
+      .unwrap_or(default_config.custom_idents),
+    dashed_idents: options
+      .dashed_idents
+      .unwrap_or(default_config.dashed_idents),
+    container: options.container.unwrap_or(default_config.container),
+    ..default_config
+  })
 }
 
 /// Validates a theme token string to ensure it follows the expected format
@@ -131,10 +206,16 @@ fn is_valid_theme_token(token: &str) -> bool {
 ///
 /// # Arguments
 /// * `parser` - CSS parser positioned at the start of the content to process
+/// * `theme_tokens` - Receives the replaced theme tokens, without duplicates
 ///
 /// # Returns
 /// * `String` - CSS with theme tokens replaced by CSS custom properties
-fn replace_theme_tokens(parser: &mut Parser<'_, '_>, theme: &Option<Theme>) -> String {
+fn replace_theme_tokens(
+  parser: &mut Parser<'_, '_>,
+  theme: &Option<Theme>,
+  diagnostics: &mut Vec<CssDiagnostic>,
+  theme_tokens: &mut Vec<String>,
+) -> String {
   let mut out = String::from("");
 
   // Track the location where a potential theme variable started (after seeing '$')
@@ -160,8 +241,13 @@ fn replace_theme_tokens(parser: &mut Parser<'_, '_>, theme: &Option<Theme>) -> S
       | Token::SquareBracketBlock => {
         if let Token::CurlyBracketBlock = token_clone {
           if let Some(last_screen_at_rule) = last_screen_at_rule_location {
-            let rule_out =
-              handle_at_rule_tokens(parser, &mut tokens_stack, last_screen_at_rule, breakpoints);
+            let rule_out = handle_at_rule_tokens(
+              parser,
+              &mut tokens_stack,
+              last_screen_at_rule,
+              breakpoints,
+              diagnostics,
+            );
             out.push_str(&rule_out);
           }
         }
@@ -184,7 +270,7 @@ fn replace_theme_tokens(parser: &mut Parser<'_, '_>, theme: &Option<Theme>) -> S
 
         // Recursively process the contents of the block
         let _ = parser.parse_nested_block(|block| {
-          let block_out = replace_theme_tokens(block, theme);
+          let block_out = replace_theme_tokens(block, theme, diagnostics, theme_tokens);
           out.push_str(&block_out);
           Ok::<(), ParseError<'_, Infallible>>(())
         });
@@ -255,6 +341,8 @@ fn replace_theme_tokens(parser: &mut Parser<'_, '_>, theme: &Option<Theme>) -> S
             &mut tokens_stack,
             last_var_location,
             theme,
+            diagnostics,
+            theme_tokens,
           );
           out.push_str(&theme_out);
           // Reset variable tracking
@@ -272,6 +360,8 @@ fn replace_theme_tokens(parser: &mut Parser<'_, '_>, theme: &Option<Theme>) -> S
             &mut tokens_stack,
             last_var_location,
             theme,
+            diagnostics,
+            theme_tokens,
           );
           out.push_str(&theme_out);
           // Reset variable tracking
@@ -296,6 +386,8 @@ fn handle_theme_tokens(
   tokens_stack: &mut Vec<(Token, SourceLocation)>,
   var_start_location: SourceLocation,
   theme: &Option<Theme>,
+  diagnostics: &mut Vec<CssDiagnostic>,
+  theme_tokens: &mut Vec<String>,
 ) -> String {
   let mut out = String::from("");
   let mut fallback_string = String::from("");
@@ -343,10 +435,22 @@ fn handle_theme_tokens(
     // - "$spaces.4" -> "var(--spaces-4)"
     let parsed_token = if is_valid_theme_token(raw_theme_token) {
       let path_vec: Vec<&str> = raw_theme_token.split(".").collect();
+      if !theme_tokens.iter().any(|token| token == raw_theme_token) {
+        theme_tokens.push(raw_theme_token.to_string());
+      }
       format!("var(--{token_prefix}{})", path_vec.join("-"))
     } else {
-      // Invalid theme token format - log warning and output as fallback
-      log_error!("Warning: Invalid theme token format '{}'. Expected format: $identifier or $identifier.segment.value (camelCase recommended)", raw_theme_token);
+      // Invalid theme token format - report it and output as fallback
+      diagnostics.push(
+        CssDiagnostic::error(
+          DiagnosticCode::InvalidThemeToken,
+          format!("Invalid theme token format '${}'", raw_theme_token),
+        )
+        .at(Some(var_start_location))
+        .with_help(
+          "Expected format: $identifier or $identifier.segment.value (camelCase recommended)",
+        ),
+      );
       // This preserves the original token in case of malformed syntax
       fallback_string.clone()
     };
@@ -372,6 +476,7 @@ fn handle_at_rule_tokens(
   tokens_stack: &mut Vec<(Token, SourceLocation)>,
   screen_at_rule_start_location: SourceLocation,
   breakpoints: &HashMap<String, String>,
+  diagnostics: &mut Vec<CssDiagnostic>,
 ) -> String {
   let mut fallback_string = String::from("");
   let mut out = String::from("");
@@ -399,9 +504,20 @@ fn handle_at_rule_tokens(
     if let Some(breakpoint_value) = breakpoints.get(rule.trim()) {
       out.push_str(&format!("@media (min-width: {})", breakpoint_value));
     } else {
-      log_error!(
-        "Error: No matching breakpoint found for '@screen {}'",
-        rule.trim()
+      let mut known_breakpoints: Vec<&str> = breakpoints.keys().map(String::as_str).collect();
+      known_breakpoints.sort();
+      let help = if known_breakpoints.is_empty() {
+        "Define the breakpoint in `theme.breakpoints`".to_string()
+      } else {
+        format!("Available breakpoints: {}", known_breakpoints.join(", "))
+      };
+      diagnostics.push(
+        CssDiagnostic::error(
+          DiagnosticCode::UnknownBreakpoint,
+          format!("No matching breakpoint found for '@screen {}'", rule.trim()),
+        )
+        .at(Some(screen_at_rule_start_location))
+        .with_help(help),
       );
       out.push_str(&fallback_string);
     }
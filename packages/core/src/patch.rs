@@ -0,0 +1,245 @@
+use oxc::span::Span;
+use parcel_sourcemap::{OriginalLocation, SourceMap};
+use serde_json::json;
+
+use crate::css_source_map::LineIndex;
+
+/// Controls how the transformed code is produced.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum CodeOutput {
+  /// Reprint the whole program with oxc's codegen (default)
+  Codegen,
+  /// Apply the recorded edits to the original source text.
+  /// Formatting and comments are kept, and every line keeps its original line number.
+  Patch,
+}
+
+impl CodeOutput {
+  /// Resolves the code output mode from the `code_output` option.
+  /// Unknown values fall back to [`CodeOutput::Codegen`].
+  pub fn from_option(value: Option<&str>) -> Self {
+    match value {
+      Some("patch") => CodeOutput::Patch,
+      _ => CodeOutput::Codegen,
+    }
+  }
+}
+
+/// A text edit on the original source, by byte offsets
+#[derive(Debug, Clone)]
+struct Patch {
+  start: u32,
+  end: u32,
+  /// Replacement text. `None` removes the span, leaving only its line breaks behind
+  text: Option<String>,
+}
+
+/// Edits recorded while the AST is transformed, so they can be applied to the original source text
+/// instead of reprinting the whole program.
+#[derive(Debug, Default)]
+pub struct Patches {
+  patches: Vec<Patch>,
+}
+
+/// Result of applying [`Patches`] to the original source
+pub struct PatchedCode {
+  pub code: String,
+  pub sourcemap: String,
+}
+
+impl Patches {
+  /// Replaces the text of `span` with `text`.
+  /// A later replacement of the same span overrides the earlier one, as the AST node was updated again.
+  pub fn replace(&mut self, span: Span, text: String) {
+    if span.is_unspanned() {
+      // Nodes created by the transform have no source text to replace
+      return;
+    }
+
+    if let Some(patch) = self
+      .patches
+      .iter_mut()
+      .find(|patch| patch.start == span.start && patch.end == span.end)
+    {
+      patch.text = Some(text);
+      return;
+    }
+
+    self.patches.push(Patch {
+      start: span.start,
+      end: span.end,
+      text: Some(text),
+    });
+  }
+
+  /// Removes the text of `span`. Line breaks are kept so the following code keeps its line numbers.
+  pub fn remove(&mut self, span: Span) {
+    if span.is_unspanned() {
+      return;
+    }
+
+    self.patches.push(Patch {
+      start: span.start,
+      end: span.end,
+      text: None,
+    });
+  }
+
+  /// Inserts `text` at `offset`
+  pub fn insert(&mut self, offset: u32, text: String) {
+    self.patches.push(Patch {
+      start: offset,
+      end: offset,
+      text: Some(text),
+    });
+  }
+
+  /// Moves the edits of `other` over. Replacements of a span override earlier ones like in
+  /// [`Patches::replace`], insertions are kept next to any others at the same offset.
+  pub fn extend(&mut self, other: Patches) {
+    for patch in other.patches {
+      match patch.text {
+        Some(text) if patch.start != patch.end => {
+          self.replace(Span::new(patch.start, patch.end), text)
+        }
+        _ => self.patches.push(patch),
+      }
+    }
+  }
+
+  /// Applies the edits to `source` and builds a source map from the result back to `source`.
+  /// Edits that overlap an earlier edit (eg: a literal inside a removed statement) are skipped.
+  pub fn apply(mut self, source: &str, source_path: &str) -> PatchedCode {
+    // Stable sort, so insertions at the same offset keep their order
+    self.patches.sort_by_key(|patch| (patch.start, patch.end));
+
+    let source_index = LineIndex::new(source);
+    let mut printer = PatchPrinter::new(source);
+    let mut cursor = 0;
+
+    for patch in self.patches {
+      let (start, end) = (patch.start as usize, patch.end as usize);
+      if start < cursor || end > source.len() {
+        continue;
+      }
+
+      printer.copy(cursor, start, &source_index);
+
+      let (original_line, original_column) = source_index.position(start);
+      printer.add_mapping(original_line, original_column);
+      match patch.text {
+        Some(text) => printer.push(&text),
+        None => printer.push(&"\n".repeat(source[start..end].matches('\n').count())),
+      }
+
+      cursor = end;
+    }
+
+    printer.copy(cursor, source.len(), &source_index);
+    printer.finish(source_path)
+  }
+}
+
+/// Writes the patched code and tracks the generated position for the source map
+struct PatchPrinter<'a> {
+  source: &'a str,
+  code: String,
+  source_map: SourceMap,
+  generated_line: u32,
+  /// Column in UTF-16 code units
+  generated_column: u32,
+}
+
+impl<'a> PatchPrinter<'a> {
+  fn new(source: &'a str) -> Self {
+    Self {
+      source,
+      code: String::with_capacity(source.len()),
+      source_map: SourceMap::new("/"),
+      generated_line: 0,
+      generated_column: 0,
+    }
+  }
+
+  fn add_mapping(&mut self, original_line: u32, original_column: u32) {
+    self.source_map.add_mapping(
+      self.generated_line,
+      self.generated_column,
+      Some(OriginalLocation::new(
+        original_line,
+        original_column,
+        0,
+        None,
+      )),
+    );
+  }
+
+  fn push(&mut self, text: &str) {
+    for ch in text.chars() {
+      self.advance(ch);
+    }
+    self.code.push_str(text);
+  }
+
+  fn advance(&mut self, ch: char) {
+    if ch == '\n' {
+      self.generated_line += 1;
+      self.generated_column = 0;
+    } else {
+      self.generated_column += ch.len_utf16() as u32;
+    }
+  }
+
+  /// Copies the unchanged source between `start` and `end`.
+  /// A mapping is added at the start of every line and every word, so columns stay exact.
+  fn copy(&mut self, start: usize, end: usize, source_index: &LineIndex) {
+    if start >= end {
+      return;
+    }
+
+    let (mut original_line, mut original_column) = source_index.position(start);
+    let mut previous: Option<char> = None;
+
+    for ch in self.source[start..end].chars() {
+      let is_word_start = !ch.is_whitespace()
+        && previous.is_none_or(|previous| previous.is_whitespace() || !is_word_char(previous));
+      if is_word_start {
+        self.add_mapping(original_line, original_column);
+      }
+
+      self.advance(ch);
+      if ch == '\n' {
+        original_line += 1;
+        original_column = 0;
+      } else {
+        original_column += ch.len_utf16() as u32;
+      }
+      previous = Some(ch);
+    }
+
+    self.code.push_str(&self.source[start..end]);
+  }
+
+  fn finish(mut self, source_path: &str) -> PatchedCode {
+    let mut mappings = vec![];
+    // Writing to a Vec can't fail
+    let _ = self.source_map.write_vlq(&mut mappings);
+
+    let sourcemap = json!({
+      "version": 3,
+      "sources": [source_path],
+      "sourcesContent": [self.source],
+      "names": [],
+      "mappings": String::from_utf8_lossy(&mappings),
+    });
+
+    PatchedCode {
+      code: self.code,
+      sourcemap: sourcemap.to_string(),
+    }
+  }
+}
+
+fn is_word_char(ch: char) -> bool {
+  ch.is_alphanumeric() || ch == '_' || ch == '$'
+}
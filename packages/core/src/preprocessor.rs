@@ -0,0 +1,16 @@
+/// Hook that rewrites the CSS of a component before it is parsed, eg: to run PostCSS or Sass.
+///
+/// Errors are reported as warnings and the original CSS is used instead.
+pub trait CssPreprocessor {
+  fn process(&self, css: &str) -> Result<String, String>;
+}
+
+/// Any `Fn(&str) -> Result<String, String>` closure can be used as a preprocessor
+impl<F> CssPreprocessor for F
+where
+  F: Fn(&str) -> Result<String, String>,
+{
+  fn process(&self, css: &str) -> Result<String, String> {
+    self(css)
+  }
+}
@@ -0,0 +1,134 @@
+use parcel_sourcemap::{Mapping, OriginalLocation, SourceMap};
+use serde_json::{json, Value};
+
+/// Composes `map`, the source map of the transformed code, with `input_map`, the source map of the
+/// code that was passed to the transform, eg: from an MDX or decorator transform that ran earlier.
+///
+/// The result maps the transformed code straight to the original sources of `input_map`,
+/// keeping its `sources`, `sourcesContent` and `names`.
+/// Positions that are not covered by `input_map` are dropped from the result.
+pub fn compose_source_maps(map: &str, input_map: &str) -> Result<String, String> {
+  let map_json: Value = serde_json::from_str(map)
+    .map_err(|err| format!("Failed to parse the generated source map: {}", err))?;
+  let input_json: Value = serde_json::from_str(input_map)
+    .map_err(|err| format!("Failed to parse the input source map: {}", err))?;
+
+  if input_json.get("sections").is_some() {
+    return Err("Indexed input source maps are not supported".to_string());
+  }
+
+  let input_lines = group_by_line(decode_mappings(&input_json)?);
+
+  let mut composed = SourceMap::new("/");
+  for mapping in decode_mappings(&map_json)? {
+    let Some(original) = mapping.original else {
+      continue;
+    };
+    let Some(input_original) = find_original_location(
+      &input_lines,
+      original.original_line,
+      original.original_column,
+    ) else {
+      continue;
+    };
+
+    composed.add_mapping(
+      mapping.generated_line,
+      mapping.generated_column,
+      Some(input_original),
+    );
+  }
+
+  let mut mappings = vec![];
+  composed
+    .write_vlq(&mut mappings)
+    .map_err(|err| format!("Failed to encode the composed source map: {}", err))?;
+  let mappings = String::from_utf8(mappings)
+    .map_err(|err| format!("Failed to encode the composed source map: {}", err))?;
+
+  let sources = input_json.get("sources").cloned().unwrap_or(json!([]));
+  let sources_len = sources.as_array().map(Vec::len).unwrap_or(0);
+  let sources_content = input_json
+    .get("sourcesContent")
+    .cloned()
+    .unwrap_or_else(|| Value::Array(vec![Value::Null; sources_len]));
+
+  let mut composed_json = json!({
+    "version": 3,
+    "sources": sources,
+    "sourcesContent": sources_content,
+    "names": input_json.get("names").cloned().unwrap_or(json!([])),
+    "mappings": mappings,
+  });
+  if let Some(file) = map_json.get("file") {
+    composed_json["file"] = file.clone();
+  }
+  if let Some(source_root) = input_json.get("sourceRoot") {
+    composed_json["sourceRoot"] = source_root.clone();
+  }
+
+  Ok(composed_json.to_string())
+}
+
+/// Decodes the `mappings` of a JSON source map.
+/// Source and name indexes are kept as-is, so they refer to the `sources` and `names` of the JSON.
+fn decode_mappings(json: &Value) -> Result<Vec<Mapping>, String> {
+  let mappings = json
+    .get("mappings")
+    .and_then(Value::as_str)
+    .ok_or("Source map has no `mappings`")?;
+  let count = |key: &str| json.get(key).and_then(Value::as_array).map(Vec::len);
+
+  // Sources and names are only needed as indexes, distinct placeholders keep the indexes stable
+  let sources: Vec<String> = (0..count("sources").unwrap_or(0))
+    .map(|index| index.to_string())
+    .collect();
+  let names: Vec<String> = (0..count("names").unwrap_or(0))
+    .map(|index| index.to_string())
+    .collect();
+
+  let mut source_map = SourceMap::new("/");
+  source_map
+    .add_vlq_map(mappings.as_bytes(), sources, vec![], names, 0, 0)
+    .map_err(|err| format!("Failed to decode source map mappings: {}", err))?;
+
+  Ok(source_map.get_mappings())
+}
+
+/// Groups mappings by generated line, sorted by generated column
+fn group_by_line(mappings: Vec<Mapping>) -> Vec<Vec<(u32, OriginalLocation)>> {
+  let mut lines: Vec<Vec<(u32, OriginalLocation)>> = vec![];
+
+  for mapping in mappings {
+    let Some(original) = mapping.original else {
+      continue;
+    };
+    let line = mapping.generated_line as usize;
+    if lines.len() <= line {
+      lines.resize_with(line + 1, Vec::new);
+    }
+    lines[line].push((mapping.generated_column, original));
+  }
+
+  for line in lines.iter_mut() {
+    line.sort_by_key(|(column, _)| *column);
+  }
+
+  lines
+}
+
+/// Finds the original location of the closest mapping at or before `column` on `line`.
+/// Columns before the first mapping (eg: indentation) use the first mapping of the line.
+fn find_original_location(
+  lines: &[Vec<(u32, OriginalLocation)>],
+  line: u32,
+  column: u32,
+) -> Option<OriginalLocation> {
+  let mappings = lines.get(line as usize)?;
+  let index = mappings.partition_point(|(mapping_column, _)| *mapping_column <= column);
+
+  index
+    .checked_sub(1)
+    .map(|index| mappings[index].1)
+    .or_else(|| mappings.first().map(|(_, original)| *original))
+}
@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+use oxc::ast::ast::{
+  BinaryExpression, BindingPatternKind, Declaration, Expression, ObjectExpression,
+  ObjectPropertyKind, Program, PropertyKey, Statement, TemplateLiteral, UnaryOperator,
+  VariableDeclarationKind,
+};
+use oxc::semantic::{Scoping, SymbolId};
+use oxc::span::{GetSpan, Span};
+use oxc::syntax::operator::BinaryOperator;
+
+use crate::css_source_map::CssSourceMapping;
+use crate::dynamic_style::DynamicValues;
+
+/// A value known at build time
+#[derive(Debug, Clone, PartialEq)]
+pub enum StaticValue {
+  String(String),
+  Number(f64),
+  /// An object or array declared `as const`, by property name or index
+  Object(IndexMap<String, StaticValue>),
+}
+
+impl StaticValue {
+  /// Converts the value to a string like JS does, eg: `4` -> `"4"`.
+  /// Objects have no useful string form and return `None`.
+  pub fn to_js_string(&self) -> Option<String> {
+    match self {
+      StaticValue::String(value) => Some(value.clone()),
+      StaticValue::Number(value) => Some(format_number(*value)),
+      StaticValue::Object(_) => None,
+    }
+  }
+}
+
+/// An expression that can't be evaluated at build time
+#[derive(Debug, Clone)]
+pub struct NonStaticExpression {
+  /// Span of the part of the expression that isn't static
+  pub span: Span,
+  pub reason: String,
+}
+
+impl NonStaticExpression {
+  fn new(span: Span, reason: impl Into<String>) -> Self {
+    Self {
+      span,
+      reason: reason.into(),
+    }
+  }
+}
+
+/// Evaluates expressions at build time, eg: the interpolations of the CSS in a template literal.
+///
+/// Supported are string and number literals, module-level `const` declarations with a static value,
+/// arithmetic on numbers, string concatenation, template literals and member access on objects that
+/// are declared `as const`. Identifiers are resolved through [`Scoping`], so a local variable that
+/// shadows a module-level `const` is not mistaken for it.
+pub struct StaticEvaluator<'s> {
+  scoping: &'s Scoping,
+  /// Values of the module-level `const` declarations, see [`collect_constants`]
+  constants: &'s HashMap<SymbolId, StaticValue>,
+}
+
+impl<'s> StaticEvaluator<'s> {
+  pub fn new(scoping: &'s Scoping, constants: &'s HashMap<SymbolId, StaticValue>) -> Self {
+    Self { scoping, constants }
+  }
+
+  pub fn evaluate(&self, expression: &Expression) -> Result<StaticValue, NonStaticExpression> {
+    self.evaluate_expression(expression, false)
+  }
+
+  /// `readonly` is set inside an `as const` expression, where object literals can't be mutated
+  fn evaluate_expression(
+    &self,
+    expression: &Expression,
+    readonly: bool,
+  ) -> Result<StaticValue, NonStaticExpression> {
+    match expression {
+      Expression::StringLiteral(string) => Ok(StaticValue::String(string.value.to_string())),
+      Expression::NumericLiteral(number) => Ok(StaticValue::Number(number.value)),
+      Expression::TemplateLiteral(template) => {
+        let mut value = String::new();
+        for (index, quasi) in template.quasis.iter().enumerate() {
+          let Some(cooked) = &quasi.value.cooked else {
+            return Err(NonStaticExpression::new(
+              quasi.span,
+              "the template contains an invalid escape sequence",
+            ));
+          };
+          value.push_str(cooked.as_str());
+          if let Some(expression) = template.expressions.get(index) {
+            value.push_str(&self.evaluate_to_string(expression)?);
+          }
+        }
+        Ok(StaticValue::String(value))
+      }
+      Expression::Identifier(identifier) => self
+        .scoping
+        .get_reference(identifier.reference_id())
+        .symbol_id()
+        .and_then(|symbol_id| self.constants.get(&symbol_id))
+        .cloned()
+        .ok_or_else(|| {
+          NonStaticExpression::new(
+            identifier.span,
+            format!(
+              "`{}` is not a module-level `const` with a static value",
+              identifier.name
+            ),
+          )
+        }),
+      Expression::ParenthesizedExpression(parenthesized) => {
+        self.evaluate_expression(&parenthesized.expression, readonly)
+      }
+      Expression::TSAsExpression(ts_as) => self.evaluate_expression(
+        &ts_as.expression,
+        readonly || ts_as.type_annotation.is_const_type_reference(),
+      ),
+      Expression::TSSatisfiesExpression(satisfies) => {
+        self.evaluate_expression(&satisfies.expression, readonly)
+      }
+      Expression::TSNonNullExpression(non_null) => {
+        self.evaluate_expression(&non_null.expression, readonly)
+      }
+      Expression::UnaryExpression(unary) => {
+        let sign = match unary.operator {
+          UnaryOperator::UnaryNegation => -1.0,
+          UnaryOperator::UnaryPlus => 1.0,
+          _ => {
+            return Err(NonStaticExpression::new(
+              unary.span,
+              format!(
+                "the `{}` operator is not supported",
+                unary.operator.as_str()
+              ),
+            ))
+          }
+        };
+        let value = self.evaluate_expression(&unary.argument, readonly)?;
+        let number = self.to_number(&value, unary.argument.span())?;
+        Ok(StaticValue::Number(sign * number))
+      }
+      Expression::BinaryExpression(binary) => self.evaluate_binary_expression(binary),
+      Expression::ObjectExpression(object) if readonly => self.evaluate_object(object),
+      // Arrays are objects indexed by position, eg: `SIZES[1]`
+      Expression::ArrayExpression(array) if readonly => {
+        let mut items = IndexMap::new();
+        for (index, element) in array.elements.iter().enumerate() {
+          let Some(element) = element.as_expression() else {
+            return Err(NonStaticExpression::new(
+              element.span(),
+              "spread elements and holes are not supported",
+            ));
+          };
+          items.insert(index.to_string(), self.evaluate_expression(element, true)?);
+        }
+        Ok(StaticValue::Object(items))
+      }
+      Expression::ObjectExpression(_) | Expression::ArrayExpression(_) => {
+        Err(NonStaticExpression::new(
+          expression.span(),
+          "objects and arrays must be declared `as const` to be used in styles",
+        ))
+      }
+      Expression::StaticMemberExpression(member) => {
+        let object = self.evaluate_expression(&member.object, readonly)?;
+        self.get_property(&object, member.property.name.as_str(), member.span)
+      }
+      Expression::ComputedMemberExpression(member) => {
+        let object = self.evaluate_expression(&member.object, readonly)?;
+        let property = self.evaluate_to_string(&member.expression)?;
+        self.get_property(&object, &property, member.span)
+      }
+      _ => Err(NonStaticExpression::new(
+        expression.span(),
+        "the expression can't be evaluated at build time",
+      )),
+    }
+  }
+
+  /// Evaluates the expression to the string it would be interpolated as
+  pub fn evaluate_to_string(&self, expression: &Expression) -> Result<String, NonStaticExpression> {
+    let value = self.evaluate(expression)?;
+    value.to_js_string().ok_or_else(|| {
+      NonStaticExpression::new(
+        expression.span(),
+        "objects can't be interpolated, use one of their properties",
+      )
+    })
+  }
+
+  fn evaluate_binary_expression(
+    &self,
+    binary: &BinaryExpression,
+  ) -> Result<StaticValue, NonStaticExpression> {
+    let left = self.evaluate(&binary.left)?;
+    let right = self.evaluate(&binary.right)?;
+
+    // `+` concatenates as soon as one side is a string, like in JS
+    if binary.operator == BinaryOperator::Addition
+      && (matches!(left, StaticValue::String(_)) || matches!(right, StaticValue::String(_)))
+    {
+      let (Some(left), Some(right)) = (left.to_js_string(), right.to_js_string()) else {
+        return Err(NonStaticExpression::new(
+          binary.span,
+          "objects can't be concatenated",
+        ));
+      };
+      return Ok(StaticValue::String(left + &right));
+    }
+
+    let left = self.to_number(&left, binary.left.span())?;
+    let right = self.to_number(&right, binary.right.span())?;
+    let value = match binary.operator {
+      BinaryOperator::Addition => left + right,
+      BinaryOperator::Subtraction => left - right,
+      BinaryOperator::Multiplication => left * right,
+      BinaryOperator::Division => left / right,
+      BinaryOperator::Remainder => left % right,
+      _ => {
+        return Err(NonStaticExpression::new(
+          binary.span,
+          format!(
+            "the `{}` operator is not supported",
+            binary.operator.as_str()
+          ),
+        ))
+      }
+    };
+
+    if !value.is_finite() {
+      return Err(NonStaticExpression::new(
+        binary.span,
+        "the expression doesn't evaluate to a finite number",
+      ));
+    }
+
+    Ok(StaticValue::Number(value))
+  }
+
+  fn evaluate_object(&self, object: &ObjectExpression) -> Result<StaticValue, NonStaticExpression> {
+    let mut properties = IndexMap::new();
+
+    for property in &object.properties {
+      let ObjectPropertyKind::ObjectProperty(property) = property else {
+        return Err(NonStaticExpression::new(
+          property.span(),
+          "spread properties are not supported",
+        ));
+      };
+      let key = match &property.key {
+        PropertyKey::StaticIdentifier(identifier) => identifier.name.to_string(),
+        PropertyKey::StringLiteral(string) => string.value.to_string(),
+        PropertyKey::NumericLiteral(number) => format_number(number.value),
+        key => {
+          return Err(NonStaticExpression::new(
+            key.span(),
+            "computed property keys are not supported",
+          ))
+        }
+      };
+      properties.insert(key, self.evaluate_expression(&property.value, true)?);
+    }
+
+    Ok(StaticValue::Object(properties))
+  }
+
+  fn get_property(
+    &self,
+    object: &StaticValue,
+    property: &str,
+    span: Span,
+  ) -> Result<StaticValue, NonStaticExpression> {
+    let StaticValue::Object(properties) = object else {
+      return Err(NonStaticExpression::new(
+        span,
+        "only properties of `as const` objects can be accessed",
+      ));
+    };
+
+    properties.get(property).cloned().ok_or_else(|| {
+      NonStaticExpression::new(span, format!("the object has no property `{}`", property))
+    })
+  }
+
+  fn to_number(&self, value: &StaticValue, span: Span) -> Result<f64, NonStaticExpression> {
+    match value {
+      StaticValue::Number(number) => Ok(*number),
+      _ => Err(NonStaticExpression::new(
+        span,
+        "arithmetic is only supported on numbers",
+      )),
+    }
+  }
+}
+
+/// Evaluates the module-level `const` declarations of the program that have a static value,
+/// including exported ones. Declarations may refer to each other in any order.
+pub fn collect_constants(program: &Program, scoping: &Scoping) -> HashMap<SymbolId, StaticValue> {
+  let mut pending: Vec<(SymbolId, &Expression)> = vec![];
+
+  for statement in &program.body {
+    let declaration = match statement {
+      Statement::VariableDeclaration(declaration) => declaration,
+      Statement::ExportNamedDeclaration(export) => match &export.declaration {
+        Some(Declaration::VariableDeclaration(declaration)) => declaration,
+        _ => continue,
+      },
+      _ => continue,
+    };
+    if declaration.kind != VariableDeclarationKind::Const {
+      continue;
+    }
+
+    for declarator in &declaration.declarations {
+      if let (BindingPatternKind::BindingIdentifier(identifier), Some(init)) =
+        (&declarator.id.kind, &declarator.init)
+      {
+        pending.push((identifier.symbol_id(), init));
+      }
+    }
+  }
+
+  // Evaluate until no more declarations can be resolved, so that a declaration can use one
+  // that comes after it. Whatever is left refers to something that isn't static.
+  let mut constants = HashMap::new();
+  loop {
+    let evaluator = StaticEvaluator::new(scoping, &constants);
+    let mut resolved = vec![];
+    pending.retain(|(symbol_id, init)| match evaluator.evaluate(init) {
+      Ok(value) => {
+        resolved.push((*symbol_id, value));
+        false
+      }
+      Err(_) => true,
+    });
+
+    if resolved.is_empty() {
+      break;
+    }
+    constants.extend(resolved);
+  }
+
+  constants
+}
+
+/// Builds the CSS of a template literal, evaluating its interpolations with `evaluator`,
+/// and records where each piece starts in the source.
+///
+/// Eg: `` `.btn { padding: ${SPACING}px }` `` with `const SPACING = 4` -> `.btn { padding: 4px }`
+///
+/// With `dynamic_values`, an interpolation that isn't static becomes a custom property when it is
+/// in a declaration value, see [`DynamicValues::add`].
+/// Eg: `` `.btn { color: ${props => props.tone} }` `` -> `.btn { color: var(--flair-1a2b3c4d) }`
+pub fn get_template_css(
+  template: &TemplateLiteral,
+  evaluator: &StaticEvaluator,
+  mut dynamic_values: Option<&mut DynamicValues>,
+) -> Result<(String, CssSourceMapping), NonStaticExpression> {
+  let mut css = String::new();
+  let mut mapping = CssSourceMapping::default();
+  // Length of the unit that was moved into the previous runtime value, eg: `px` in `${size}px`
+  let mut unit_length = 0;
+
+  for (index, quasi) in template.quasis.iter().enumerate() {
+    mapping.push_verbatim(css.len(), quasi.span.start + unit_length as u32);
+    css.push_str(&quasi.value.raw.as_str()[unit_length..]);
+    unit_length = 0;
+
+    let Some(expression) = template.expressions.get(index) else {
+      continue;
+    };
+    // The value doesn't exist in the source, so all of it maps to the expression
+    mapping.push_generated(css.len(), expression.span().start);
+    let err = match evaluator.evaluate_to_string(expression) {
+      Ok(value) => {
+        css.push_str(&value);
+        continue;
+      }
+      Err(err) => err,
+    };
+
+    let Some(dynamic_values) = dynamic_values.as_deref_mut() else {
+      return Err(err);
+    };
+    let css_after = template
+      .quasis
+      .get(index + 1)
+      .map(|quasi| quasi.value.raw.as_str())
+      .unwrap_or_default();
+    let value = dynamic_values
+      .add(expression, &css, css_after)
+      .map_err(|reason| {
+        NonStaticExpression::new(err.span, format!("{}, and {}", err.reason, reason))
+      })?;
+    unit_length = value.unit.len();
+    css.push_str(&format!("var({})", value.name));
+  }
+
+  Ok((css, mapping))
+}
+
+/// Formats a number like JS does for the common cases, eg: `4.0` -> `4`, `-0` -> `0`
+fn format_number(value: f64) -> String {
+  if value == 0.0 {
+    return "0".to_string();
+  }
+  format!("{}", value)
+}
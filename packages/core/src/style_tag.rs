@@ -7,6 +7,10 @@ use oxc::{
   semantic::{Scoping, SymbolId},
 };
 
+use crate::css_source_map::CssSourceMapping;
+use crate::dynamic_style::DynamicValues;
+use crate::imports::is_jsx_namespace_member;
+use crate::static_eval::{get_template_css, NonStaticExpression, StaticEvaluator};
 use crate::transform::CSSData;
 
 pub struct StyleDetector<'a> {
@@ -14,36 +18,112 @@ pub struct StyleDetector<'a> {
   /// Vector of symbol IDs that represent imported style tag components
   /// eg import { Style } from "@flairjs/client/react"
   style_tag_import_symbols: &'a Vec<SymbolId>,
+  /// Namespace and default imports of flair modules, eg: `F` for `<F.Style>`
+  namespace_symbols: &'a Vec<SymbolId>,
+  /// Names of the Style component, used for members of `namespace_symbols`
+  style_names: &'a [String],
   /// Collection of span start positions for detected style tag elements
   /// Used to track where style elements are located in the source code
   /// This will be used to delete the style elements after extraction
   style_tag_symbol_ids: Vec<u32>,
   /// Extracted CSS data from all detected style elements
   pub css: Vec<CSSData>,
-  /// Flag indicating whether any style elements were found during traversal
-  pub has_style: bool,
+  /// Evaluates the interpolations of template literals
+  evaluator: &'a StaticEvaluator<'a>,
+  /// Project-relative path of the file, names the custom properties of runtime values
+  file_id: &'a str,
+  /// Interpolations that couldn't be evaluated, their style elements are skipped
+  pub errors: Vec<NonStaticExpression>,
 }
 
 impl StyleDetector<'_> {
   pub fn new<'a>(
     scoping: &'a Scoping,
     style_tag_import_symbols: &'a Vec<SymbolId>,
+    namespace_symbols: &'a Vec<SymbolId>,
+    style_names: &'a [String],
+    evaluator: &'a StaticEvaluator<'a>,
+    file_id: &'a str,
   ) -> StyleDetector<'a> {
-    let has_style = false;
     let css = vec![];
     let style_tag_symbol_ids = vec![];
 
     StyleDetector {
-      has_style,
       css,
       scoping,
       style_tag_import_symbols,
+      namespace_symbols,
+      style_names,
       style_tag_symbol_ids,
+      evaluator,
+      file_id,
+      errors: vec![],
     }
   }
 
-  pub fn get_style_tag_symbol_ids(&self) -> Vec<u32> {
-    self.style_tag_symbol_ids.to_vec()
+  /// Returns every detected style element as its span start and extracted CSS, in source order
+  pub fn into_style_tags(self) -> Vec<(u32, CSSData)> {
+    // Both are pushed once per detected style element
+    self
+      .style_tag_symbol_ids
+      .into_iter()
+      .zip(self.css)
+      .collect()
+  }
+}
+
+impl StyleDetector<'_> {
+  /// Extracts the CSS from the children of a style element
+  fn extract_css(&self, jsx: &JSXElement<'_>) -> Result<CSSData, NonStaticExpression> {
+    let mut extracted_css: String = "".to_string();
+    // Tracks where each child's CSS starts in the source, used for CSS source maps
+    let mut source = CssSourceMapping::default();
+
+    // Check if this style element should be treated as global CSS
+    let is_global = check_if_global(jsx);
+    // Runtime values need elements to be set on, so only scoped styles can use them
+    let mut dynamic_values = DynamicValues::new(self.file_id, false);
+
+    // Extract CSS content from the children of the styled component
+    for child in jsx.children.iter() {
+      // Handle direct text content (e.g., <Style>body { color: red; }</Style>)
+      if let JSXChild::Text(child_text) = child {
+        source.push_verbatim(extracted_css.len(), child_text.span.start);
+        extracted_css.push_str(&child_text.value);
+      }
+      // Handle JavaScript expressions containing CSS (e.g., <Style>{`body { color: red; }`}</Style>)
+      else if let JSXChild::ExpressionContainer(child_expression) = child {
+        let expression = &child_expression.expression;
+        if let JSXExpression::TemplateLiteral(template_expression) = expression {
+          // Join the quasis of the template literal with the values of its interpolations
+          let (template_css, template_source) = get_template_css(
+            template_expression,
+            self.evaluator,
+            (!is_global).then_some(&mut dynamic_values),
+          )?;
+
+          source.extend(&template_source, extracted_css.len());
+          extracted_css.push_str(&template_css);
+        } else if let JSXExpression::TaggedTemplateExpression(tagged_template) = expression {
+          // Handle tagged template literals (e.g., css`body { color: red; }`)
+          let (template_css, template_source) = get_template_css(
+            &tagged_template.quasi,
+            self.evaluator,
+            (!is_global).then_some(&mut dynamic_values),
+          )?;
+
+          source.extend(&template_source, extracted_css.len());
+          extracted_css.push_str(&template_css);
+        }
+      }
+    }
+
+    Ok(CSSData {
+      raw_css: extracted_css,
+      is_global,
+      source,
+      dynamic_values: dynamic_values.values,
+    })
   }
 }
 
@@ -51,67 +131,33 @@ impl<'a> Visit<'_> for StyleDetector<'a> {
   fn visit_jsx_element(&mut self, jsx: &JSXElement<'_>) {
     let name = &jsx.opening_element.name;
 
-    if let JSXElementName::IdentifierReference(ident) = name {
-      // Resolve the identifier to its symbol using the scoping context
-      let reference = self.scoping.get_reference(ident.reference_id());
-      let Some(symbol_id) = reference.symbol_id() else {
-        walk::walk_jsx_element(self, jsx);
-        return;
-      };
-
-      // Check if this symbol matches any of the imported style tag symbols
-      if self.style_tag_import_symbols.contains(&symbol_id) {
-        self.has_style = true;
-
-        // Store the span start position for this style element
-        // This helps track where the styled component appears in source code so it can be removed later
-        self.style_tag_symbol_ids.push(jsx.span.start);
-
-        let children_iter = jsx.children.iter();
-
-        let mut extracted_css: String = "".to_string();
-
-        // Check if this style element should be treated as global CSS
-        let is_global = check_if_global(jsx);
-
-        // Extract CSS content from the children of the styled component
-        for child in children_iter {
-          // Handle direct text content (e.g., <Style>body { color: red; }</Style>)
-          if let JSXChild::Text(child_text) = child {
-            extracted_css.push_str(&child_text.value);
-          }
-          // Handle JavaScript expressions containing CSS (e.g., <Style>{`body { color: red; }`}</Style>)
-          else if let JSXChild::ExpressionContainer(child_expression) = child {
-            let expression = &child_expression.expression;
-            if let JSXExpression::TemplateLiteral(template_expression) = expression {
-              // Extract the raw string content from template literal quasi elements
-              // Note: This only extracts static parts, not interpolated expressions
-              let template_expression_value = template_expression
-                .quasis
-                .iter()
-                .map(|elem| elem.value.clone().raw.into_string())
-                .collect::<Vec<String>>()
-                .join("");
-
-              extracted_css.push_str(&template_expression_value);
-            } else if let JSXExpression::TaggedTemplateExpression(tagged_template) = expression {
-              // Handle tagged template literals (e.g., css`body { color: red; }`)
-              let tagged_template_value = tagged_template
-                .quasi
-                .quasis
-                .iter()
-                .map(|elem| elem.value.clone().raw.into_string())
-                .collect::<Vec<String>>()
-                .join("");
-
-              extracted_css.push_str(&tagged_template_value);
-            }
-          }
+    let is_style_tag = match name {
+      // Resolve the identifier to its symbol and check if it is an imported style tag symbol
+      JSXElementName::IdentifierReference(ident) => self
+        .scoping
+        .get_reference(ident.reference_id())
+        .symbol_id()
+        .is_some_and(|symbol_id| self.style_tag_import_symbols.contains(&symbol_id)),
+      // `<F.Style>`, where `F` is a namespace or default import of a flair module
+      JSXElementName::MemberExpression(member) => is_jsx_namespace_member(
+        self.scoping,
+        self.namespace_symbols,
+        member,
+        self.style_names,
+      ),
+      _ => false,
+    };
+
+    if is_style_tag {
+      match self.extract_css(jsx) {
+        Ok(css_data) => {
+          // Store the span start position for this style element
+          // This helps track where the styled component appears in source code so it can be removed later
+          self.style_tag_symbol_ids.push(jsx.span.start);
+          self.css.push(css_data);
         }
-        self.css.push(CSSData {
-          raw_css: extracted_css,
-          is_global,
-        });
+        // The element is left in the code, as its CSS couldn't be extracted
+        Err(err) => self.errors.push(err),
       }
     }
 
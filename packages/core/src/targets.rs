@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use lightningcss::targets::{Browsers, Features, Targets};
+use once_cell::sync::Lazy;
+
+use crate::css_output::absolute_path;
+use crate::log_warn;
+use crate::logger::{DiagnosticCode, Logger};
+use crate::transform::CssTargets;
+
+/// Browserslist query used when neither `browserslist` nor a discovered config is available.
+/// "defaults" refers to browserslist's default query (last 2 versions, >0.2% usage, not dead)
+const DEFAULT_BROWSERSLIST: &str = "defaults";
+
+/// Files a directory can define its browserslist queries in, in order of precedence
+const BROWSERSLIST_CONFIG_FILES: [&str; 3] = [".browserslistrc", "browserslist", "package.json"];
+
+/// Modification times of the `BROWSERSLIST_CONFIG_FILES` of a directory, `None` for missing files
+type ConfigStamp = [Option<SystemTime>; 3];
+
+/// Queries of a directory along with the stamp of its config files when they were read
+type CachedConfig = (ConfigStamp, Option<Vec<String>>);
+
+/// Browserslist queries defined directly in a directory, by directory.
+/// Config files are only read again when their modification times change, so edits are picked up
+/// by long-running processes such as `flair --watch`.
+static BROWSERSLIST_CACHE: Lazy<Mutex<HashMap<PathBuf, CachedConfig>>> =
+  Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Resolves the lightningcss targets for a source file.
+///
+/// The browsers are taken from, in order:
+/// 1. `targets.browserslist`
+/// 2. `.browserslistrc` / `browserslist` / `package.json#browserslist` in the source file's
+///    directory or any parent directory, if `targets.discover_browserslist` is enabled
+/// 3. The browserslist `defaults` query
+pub fn get_targets(options: &Option<CssTargets>, file_path: &str, logger: &Logger) -> Targets {
+  let queries = options
+    .as_ref()
+    .and_then(|targets| {
+      if targets.browserslist.is_some() {
+        return targets.browserslist.clone();
+      }
+      if targets.discover_browserslist.unwrap_or(false) {
+        // Relative paths would stop the search at the current working directory
+        let file_path = absolute_path(Path::new(file_path));
+        let dir = file_path.parent().unwrap_or(Path::new("/"));
+        return discover_browserslist(dir);
+      }
+      None
+    })
+    .unwrap_or_else(|| vec![DEFAULT_BROWSERSLIST.to_string()]);
+
+  let browsers = match Browsers::from_browserslist(&queries) {
+    Ok(browsers) => browsers,
+    Err(err) => {
+      log_warn!(
+        logger,
+        DiagnosticCode::InvalidBrowserslist,
+        "Invalid browserslist query {:?}: {}. Falling back to \"{}\"",
+        queries,
+        err,
+        DEFAULT_BROWSERSLIST
+      );
+      Browsers::from_browserslist([DEFAULT_BROWSERSLIST]).unwrap_or(None)
+    }
+  };
+
+  // Nesting and media range syntax are always compiled unless configured otherwise,
+  // so nested selectors are flattened for browsers that don't support them
+  let include = match options.as_ref().and_then(|t| t.include.as_ref()) {
+    Some(names) => parse_features(names, logger),
+    None => Features::Nesting | Features::MediaRangeSyntax,
+  };
+  let exclude = options
+    .as_ref()
+    .and_then(|t| t.exclude.as_ref())
+    .map(|names| parse_features(names, logger))
+    .unwrap_or_default();
+
+  Targets {
+    browsers,
+    include,
+    exclude,
+  }
+}
+
+/// Converts lightningcss feature names (eg: `Nesting`, `VendorPrefixes`, `Colors`) to flags.
+/// Unknown names are ignored with a warning.
+fn parse_features(names: &[String], logger: &Logger) -> Features {
+  names.iter().fold(
+    Features::empty(),
+    |features, name| match Features::from_name(name) {
+      Some(feature) => features | feature,
+      None => {
+        log_warn!(
+          logger,
+          DiagnosticCode::UnknownCssFeature,
+          "Unknown CSS feature '{}' in targets, ignoring it",
+          name
+        );
+        features
+      }
+    },
+  )
+}
+
+/// Finds the closest browserslist config starting from `dir` and walking up to the file system root.
+fn discover_browserslist(dir: &Path) -> Option<Vec<String>> {
+  dir.ancestors().find_map(get_browserslist_config)
+}
+
+/// Returns the browserslist queries defined directly in `dir`, reading them only when the config
+/// files changed since the last lookup.
+fn get_browserslist_config(dir: &Path) -> Option<Vec<String>> {
+  let stamp: ConfigStamp = BROWSERSLIST_CONFIG_FILES.map(|config_file| {
+    fs::metadata(dir.join(config_file))
+      .and_then(|metadata| metadata.modified())
+      .ok()
+  });
+
+  if let Ok(cache) = BROWSERSLIST_CACHE.lock() {
+    if let Some((cached_stamp, queries)) = cache.get(dir) {
+      if *cached_stamp == stamp {
+        return queries.clone();
+      }
+    }
+  }
+
+  let queries = if stamp.iter().all(Option::is_none) {
+    None
+  } else {
+    read_browserslist_config(dir)
+  };
+
+  if let Ok(mut cache) = BROWSERSLIST_CACHE.lock() {
+    cache.insert(dir.to_path_buf(), (stamp, queries.clone()));
+  }
+
+  queries
+}
+
+/// Reads the browserslist queries defined directly in `dir`, if any.
+fn read_browserslist_config(dir: &Path) -> Option<Vec<String>> {
+  for config_file in [".browserslistrc", "browserslist"] {
+    if let Ok(content) = fs::read_to_string(dir.join(config_file)) {
+      return Some(parse_browserslistrc(&content));
+    }
+  }
+
+  let package_json = fs::read_to_string(dir.join("package.json")).ok()?;
+  let package_json: serde_json::Value = serde_json::from_str(&package_json).ok()?;
+  let browserslist = package_json.get("browserslist")?;
+
+  // `browserslist` can be a query, a list of queries or queries per environment
+  match browserslist {
+    serde_json::Value::String(query) => Some(vec![query.clone()]),
+    serde_json::Value::Array(_) => Some(json_string_list(browserslist)),
+    serde_json::Value::Object(environments) => environments
+      .get(&get_browserslist_env())
+      .or_else(|| environments.get("defaults"))
+      .map(json_string_list),
+    _ => None,
+  }
+}
+
+/// Parses a `.browserslistrc` file, only keeping the queries that apply to the current environment.
+///
+/// ```text
+/// # comment
+/// > 0.5%
+///
+/// [production]
+/// last 2 versions
+/// ```
+fn parse_browserslistrc(content: &str) -> Vec<String> {
+  let env = get_browserslist_env();
+  let mut defaults = vec![];
+  let mut env_queries: Option<Vec<String>> = None;
+  let mut current_section: Option<Vec<String>> = None;
+
+  for line in content.lines() {
+    let line = line.split('#').next().unwrap_or("").trim();
+    if line.is_empty() {
+      continue;
+    }
+
+    if line.starts_with('[') && line.ends_with(']') {
+      current_section = Some(
+        line[1..line.len() - 1]
+          .split_whitespace()
+          .map(|name| name.to_string())
+          .collect(),
+      );
+      continue;
+    }
+
+    match &current_section {
+      None => defaults.push(line.to_string()),
+      Some(section) if section.contains(&env) => env_queries
+        .get_or_insert_with(Vec::new)
+        .push(line.to_string()),
+      Some(_) => {}
+    }
+  }
+
+  env_queries.unwrap_or(defaults)
+}
+
+/// Returns the browserslist environment, following the browserslist package:
+/// `BROWSERSLIST_ENV`, then `NODE_ENV`, then `production`.
+fn get_browserslist_env() -> String {
+  std::env::var("BROWSERSLIST_ENV")
+    .or_else(|_| std::env::var("NODE_ENV"))
+    .unwrap_or_else(|_| "production".to_string())
+}
+
+fn json_string_list(value: &serde_json::Value) -> Vec<String> {
+  match value {
+    serde_json::Value::Array(items) => items
+      .iter()
+      .filter_map(|item| item.as_str().map(|query| query.to_string()))
+      .collect(),
+    serde_json::Value::String(query) => vec![query.clone()],
+    _ => vec![],
+  }
+}
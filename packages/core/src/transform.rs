@@ -1,40 +1,50 @@
-use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
-use std::fs::File;
-use std::hash::{Hash, Hasher};
-use std::io::Write;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
+use crate::css_output::{
+  absolute_path, get_css_file_name, get_css_import_specifier, get_project_relative_path,
+  get_virtual_module_id, write_css_file, CssImportSpecifier, CssOutput, ModuleFormat,
+};
+use crate::css_source_map::{
+  build_css_source_map, remap_css_mappings, CssSourceMapping, LineIndex,
+};
+use crate::dynamic_style::{bind_props, get_props_code, DynamicStyleBinder, DynamicValue};
 use crate::flair_property::{FlairProperty, FLAIR_REPLACEMENT};
+use crate::imports::FlairApi;
 use crate::log_warn;
-use crate::logger::{get_logger, LogEntry};
+use crate::logger::{DiagnosticCode, LogEntry, LogLevel, Logger};
+use crate::manifest::{update_manifest, ManifestComponent, ManifestEntry};
+use crate::parse_css::{ParseCssOptions, ParsedCss};
+use crate::patch::{CodeOutput, Patches};
+use crate::preprocessor::CssPreprocessor;
+use crate::source_map::compose_source_maps;
+use crate::static_eval::{collect_constants, NonStaticExpression, StaticEvaluator, StaticValue};
 use crate::style_tag::StyleDetector;
+use crate::targets::get_targets;
 use crate::update_attribute::ClassNameReplacer;
 use crate::{log_error, parse_css::parse_css, update_attribute::SymbolStore};
 use indexmap::IndexMap;
 use lightningcss::css_modules::CssModuleExport;
-use lightningcss::stylesheet::ToCssResult;
-use napi::bindgen_prelude::Function as NapiFunction;
-use napi::Env;
-use napi_derive::napi;
 use oxc::ast::ast::{
-  ArrowFunctionExpression, BindingPatternKind, Class, FunctionBody, ImportDeclaration,
-  ImportDeclarationSpecifier, ImportOrExportKind, ImportSpecifier, JSXChild, ModuleExportName,
-  Statement, VariableDeclaration,
+  Argument, ArrowFunctionExpression, BindingPatternKind, Class, FormalParameters, FunctionBody,
+  ImportDeclaration, ImportDeclarationSpecifier, ImportOrExportKind, ImportSpecifier, JSXChild,
+  JSXElement, JSXFragment, ModuleExportName, Statement, VariableDeclaration,
 };
 use oxc::ast::ast::{Expression, Program};
 use oxc::ast::NONE;
 use oxc::ast::{ast::Function, AstBuilder};
 use oxc::codegen::{Codegen, CodegenOptions};
-use oxc::span::SPAN;
+use oxc::span::{Span, SPAN};
 use oxc::{
-  allocator::Allocator,
+  allocator::{Allocator, Vec as ArenaVec},
   ast::ast::SourceType,
   ast_visit::{walk_mut, Visit, VisitMut},
   parser::{Parser, ParserReturn},
   semantic::{ScopeFlags, Scoping, SemanticBuilder, SymbolId},
 };
+use parcel_sourcemap::{Mapping, SourceMap};
+use rayon::prelude::*;
 
 /// Represents the different passes of the AST transformation.
 /// The transformation requires three passes due to dependency chains:
@@ -57,31 +67,241 @@ enum Pass {
   Third,
 }
 
-#[napi(object)]
+/// Controls what happens when the source code has syntax errors.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ParseErrorPolicy {
+  /// Report the errors and return the original code untransformed (default)
+  Bail,
+  /// Report the errors and transform the AST that the parser recovered.
+  /// A parser panic still bails, as nothing could be recovered.
+  Recover,
+}
+
+impl ParseErrorPolicy {
+  /// Resolves the policy from the `on_parse_error` option.
+  /// Unknown values fall back to [`ParseErrorPolicy::Bail`].
+  pub fn from_option(value: Option<&str>) -> Self {
+    match value {
+      Some("recover") => ParseErrorPolicy::Recover,
+      _ => ParseErrorPolicy::Bail,
+    }
+  }
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(
+  feature = "cli",
+  derive(serde::Deserialize),
+  serde(rename_all = "camelCase", deny_unknown_fields)
+)]
+#[derive(Clone, Default)]
 pub struct Theme {
   pub breakpoints: Option<HashMap<String, String>>,
   pub prefix: Option<String>,
 }
 
-/// The import paths for flair-related utilities and components
-const IMPORT_PATH: &str = "@flairjs/client";
+/// CSS Modules options used when scoping flair styles
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(
+  feature = "cli",
+  derive(serde::Deserialize),
+  serde(rename_all = "camelCase", deny_unknown_fields)
+)]
+#[derive(Clone, Default)]
+pub struct CssModulesOptions {
+  /// Class name pattern. Supports `[hash]`, `[local]`, `[name]` and `[content-hash]`.
+  /// Defaults to `[hash]_[local]`. Use `[local]` to keep class names as written.
+  pub pattern: Option<String>,
+  /// Whether to scope animation names. Defaults to `true`.
+  pub animation: Option<bool>,
+  /// Whether to scope grid line and area names. Defaults to `true`.
+  pub grid: Option<bool>,
+  /// Whether to scope custom identifiers. Defaults to `true`.
+  pub custom_idents: Option<bool>,
+  /// Whether to scope dashed identifiers, eg: custom properties. Defaults to `false`.
+  pub dashed_idents: Option<bool>,
+  /// Whether to scope container names. Defaults to `true`.
+  pub container: Option<bool>,
+}
 
-#[napi(object)]
+/// Browser targets and CSS features used when compiling flair styles
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(
+  feature = "cli",
+  derive(serde::Deserialize),
+  serde(rename_all = "camelCase", deny_unknown_fields)
+)]
+#[derive(Clone, Default)]
+pub struct CssTargets {
+  /// Browserslist queries, eg: `["safari >= 12", "> 0.5%"]`. Defaults to `["defaults"]`.
+  pub browserslist: Option<Vec<String>>,
+  /// Load the queries from the closest `.browserslistrc`, `browserslist` or
+  /// `package.json#browserslist`, starting from the source file's directory.
+  /// Ignored when `browserslist` is set.
+  pub discover_browserslist: Option<bool>,
+  /// lightningcss features that are always compiled, eg: `["Nesting", "VendorPrefixes"]`.
+  /// Defaults to `["Nesting", "MediaRangeSyntax"]`.
+  pub include: Option<Vec<String>>,
+  /// lightningcss features that are never compiled, eg: `["LogicalProperties"]`.
+  pub exclude: Option<Vec<String>>,
+}
+
+/// Controls the import of the generated CSS that is added to every transformed file
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Default)]
+pub struct CssImport {
+  /// `template` (default) fills the `[file]` placeholder of `template`.
+  /// `relative` imports the CSS file in `css_out_dir` relative to the source file.
+  /// `absolute` imports the CSS file in `css_out_dir` by its absolute path.
+  #[cfg_attr(
+    feature = "napi",
+    napi(ts_type = "'template' | 'relative' | 'absolute'")
+  )]
+  pub specifier: Option<String>,
+  /// Specifier template, `[file]` is replaced with the generated CSS file name.
+  /// Defaults to `@flairjs/client/generated-css/[file]`.
+  pub template: Option<String>,
+  /// `esm` (default) emits `import "..."`, `cjs` emits `require("...")`.
+  #[cfg_attr(feature = "napi", napi(ts_type = "'esm' | 'cjs'"))]
+  pub format: Option<String>,
+}
+
+/// Additional module sources and names of the flair APIs, eg: for a design system that re-exports
+/// them under its own package. The defaults are always recognised.
+#[cfg_attr(feature = "napi", napi(object))]
+#[cfg_attr(
+  feature = "cli",
+  derive(serde::Deserialize),
+  serde(rename_all = "camelCase", deny_unknown_fields)
+)]
+#[derive(Clone, Default)]
+pub struct FlairImports {
+  /// Modules the flair APIs are re-exported from, eg: `["@acme/ui/styling"]`.
+  /// Subpaths match as well, eg: `@acme/ui/styling/react`.
+  pub sources: Option<Vec<String>>,
+  /// Names of the Style component, in addition to `Style`
+  pub style: Option<Vec<String>>,
+  /// Names of the class name utilities, in addition to `c` and `cn`
+  pub class_name_utils: Option<Vec<String>>,
+  /// Names of the object helper, in addition to `flair`
+  pub flair_helper: Option<Vec<String>>,
+  /// Names of the scoped style property, in addition to `flair`
+  pub flair_property: Option<Vec<String>>,
+  /// Names of the global style property, in addition to `globalFlair`
+  pub global_flair_property: Option<Vec<String>>,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Default)]
 pub struct TransformOptions {
-  pub css_out_dir: String,
+  /// Directory the generated CSS files are written to.
+  /// Required when `css_output` is `file`.
+  pub css_out_dir: Option<String>,
   pub class_name_list: Option<Vec<String>>,
   pub use_theme: Option<bool>,
   pub theme: Option<Theme>,
+  /// Appends a millisecond timestamp to the default CSS file name.
+  /// Ignored when `css_file_name` is set, use `[contenthash]` instead.
   pub append_timestamp_to_css_file: Option<bool>,
+  /// `file` (default) writes the generated CSS to `css_out_dir`.
+  /// `virtual` writes nothing to disk and imports the CSS through `virtual_module_id`.
+  #[cfg_attr(feature = "napi", napi(ts_type = "'file' | 'virtual'"))]
+  pub css_output: Option<String>,
+  /// Module id template used for the CSS import when `css_output` is `virtual`.
+  /// `[file]` is replaced with the generated CSS file name. Defaults to `virtual:flair/[file]`.
+  pub virtual_module_id: Option<String>,
+  /// Controls the specifier and module format of the generated CSS import
+  pub css_import: Option<CssImport>,
+  /// Naming template for the generated CSS file, relative to `css_out_dir`.
+  /// Supports `[dir]`, `[name]`, `[ext]`, `[hash]` and `[contenthash]`. Defaults to `[contenthash].css`.
+  /// Eg: `[dir]/[name].[contenthash].css`
+  pub css_file_name: Option<String>,
+  /// Project root, defaults to the current working directory.
+  /// File paths are made relative to it before hashing so that generated
+  /// class names and CSS file names are identical on every machine.
+  pub root: Option<String>,
+  /// Class name pattern and scoping options for CSS Modules
+  pub css_modules: Option<CssModulesOptions>,
+  /// Browser targets and CSS features to compile the generated CSS for
+  pub targets: Option<CssTargets>,
+  /// Minify the generated CSS. Defaults to `false`, expecting the bundler to handle minification.
+  pub minify: Option<bool>,
+  /// Generate a source map for the CSS that points back into the source file.
+  /// When `css_output` is `file`, the map is written next to the CSS file as `{css_file_name}.map`.
+  pub css_sourcemap: Option<bool>,
+  /// Source map of the input code from earlier transforms, as JSON.
+  /// The returned `sourcemap` is composed with it, so it points to the original sources.
+  pub input_sourcemap: Option<String>,
+  /// `codegen` (default) reprints the whole program.
+  /// `patch` applies the edits to the original source, keeping its formatting, comments and line numbers.
+  #[cfg_attr(feature = "napi", napi(ts_type = "'codegen' | 'patch'"))]
+  pub code_output: Option<String>,
+  /// `bail` (default) returns the original code when it has syntax errors.
+  /// `recover` transforms what the parser could recover. The errors are reported in `logs` either way.
+  #[cfg_attr(feature = "napi", napi(ts_type = "'bail' | 'recover'"))]
+  pub on_parse_error: Option<String>,
+  /// Keep the `flair-manifest` directory in `css_out_dir` up to date with the CSS file and components of
+  /// every transformed file, so stale CSS files can be removed with `prune_css_files`.
+  /// Only used when `css_output` is `file`.
+  pub manifest: Option<bool>,
+  /// Additional module sources and names the flair APIs are imported as
+  pub imports: Option<FlairImports>,
 }
 
-#[napi(object)]
+#[cfg_attr(feature = "napi", napi(object))]
 pub struct TransformOutput {
   pub code: String,
   pub sourcemap: Option<String>,
   pub css: String,
   pub logs: Vec<LogEntry>,
   pub generated_css_name: Option<String>,
+  /// The module id imported by the transformed code when `css_output` is `virtual`.
+  /// Bundlers should resolve this id to `css`.
+  pub virtual_module_id: Option<String>,
+  /// Source map of `css`, when `css_sourcemap` is enabled
+  pub css_sourcemap: Option<String>,
+  /// Styles and class names of every component with styles, in source order
+  pub components: Vec<ComponentMetadata>,
+}
+
+/// Styles and class names of a component, see `TransformOutput.components`
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Debug, Clone)]
+pub struct ComponentMetadata {
+  /// Name of the component, unless it is anonymous
+  pub name: Option<String>,
+  /// Byte offset where the component function or class starts in the source
+  pub start: u32,
+  /// Byte offset where the component function or class ends in the source
+  pub end: u32,
+  /// 1-based line where the component starts
+  pub line: u32,
+  /// 1-based column where the component starts
+  pub column: u32,
+  /// Original to scoped class names, eg: `{ "button": "a1b2c3_button" }`
+  pub class_names: HashMap<String, String>,
+  /// Generated CSS of the scoped styles
+  pub scoped_css: Option<String>,
+  /// Generated CSS of the global styles
+  pub global_css: Option<String>,
+  /// Theme tokens used by the styles, without the `$`, eg: `colors.primary`
+  pub theme_tokens: Vec<String>,
+}
+
+impl TransformOutput {
+  /// Output for a file that was left as it is, carrying the logs that explain why
+  fn untransformed(code: String, logs: Vec<LogEntry>) -> Self {
+    Self {
+      code,
+      sourcemap: None,
+      css: String::new(),
+      logs,
+      generated_css_name: None,
+      virtual_module_id: None,
+      css_sourcemap: None,
+      components: vec![],
+    }
+  }
 }
 
 /// Entry point for transforming a TypeScript React file.
@@ -90,12 +310,14 @@ pub struct TransformOutput {
 /// 2. Running a three-pass transformation to extract and process CSS
 /// 3. Generating the transformed code with CSS imports
 /// 4. Writing the extracted CSS to a separate file
+///
+/// Returns `None` when the file has no styles and nothing was logged.
+/// Files that could not be transformed are returned as they are, with the logs explaining why.
 pub fn transform(
   code: String,
   file_path: String,
   options: TransformOptions,
-  css_preprocessor: Option<NapiFunction<String, String>>,
-  env: Option<Env>,
+  css_preprocessor: Option<&dyn CssPreprocessor>,
 ) -> Option<TransformOutput> {
   if !matches!(
     file_path.split('.').next_back(),
@@ -104,23 +326,57 @@ pub fn transform(
     return None;
   }
 
+  // Logs of this transform only, returned in the output
+  let logger = Logger::new();
+
   // Set up the OXC parser infrastructure
   let allocator = Allocator::default();
   let source_type = match SourceType::from_path(&file_path) {
     Ok(source_type) => source_type,
     Err(_) => {
       log_error!(
+        logger,
+        DiagnosticCode::UnsupportedSourceType,
         "Failed to determine source type from file path: {}",
         file_path
       );
-      return None;
+      return Some(TransformOutput::untransformed(code, logger.drain_logs()));
     }
   };
 
   let sourcemap_file_path = file_path.clone();
+  let input_sourcemap = options.input_sourcemap.clone();
+  let code_output = CodeOutput::from_option(options.code_output.as_deref());
+  let parse_error_policy = ParseErrorPolicy::from_option(options.on_parse_error.as_deref());
 
   // Parse the source code into an Abstract Syntax Tree (AST)
-  let ParserReturn { mut program, .. } = Parser::new(&allocator, &code, source_type).parse();
+  let ParserReturn {
+    mut program,
+    errors,
+    panicked,
+    ..
+  } = Parser::new(&allocator, &code, source_type).parse();
+
+  if !errors.is_empty() || panicked {
+    let source_index = LineIndex::new(&code);
+    for error in &errors {
+      logger.log_entry(LogEntry::from_diagnostic(
+        error,
+        DiagnosticCode::JsParseError,
+        &file_path,
+        &code,
+        &source_index,
+      ));
+    }
+
+    if panicked || parse_error_policy == ParseErrorPolicy::Bail {
+      // Transforming a broken AST could silently drop code, so leave the file as it is
+      return Some(TransformOutput::untransformed(
+        code.clone(),
+        logger.drain_logs(),
+      ));
+    }
+  }
 
   // Build semantic information (symbol tables, scopes, references)
   let semantic_builder = SemanticBuilder::new().build(&program);
@@ -128,57 +384,219 @@ pub fn transform(
   // Convert semantic info into scoping data for symbol resolution
   let scoping = semantic_builder.semantic.into_scoping();
 
-  let ast_builder = AstBuilder::new(&allocator);
-
-  // Create the main visitor that will perform the three-pass transformation
-  let mut visitor = TransformVisitor::new(
+  let mut output = run_transform(
     &allocator,
-    ast_builder,
+    &mut program,
     &scoping,
-    file_path.clone(),
+    file_path,
     options,
-    &css_preprocessor,
-    env,
+    css_preprocessor,
+    &logger,
   );
 
-  // Execute the multi-pass transformation on the AST
-  visitor.begin(&mut program);
-
-  if visitor.extracted_css.is_empty() {
-    // No CSS was extracted, so no further processing is needed
-    return None;
+  if !output.transformed {
+    // No CSS was extracted, so no further processing is needed.
+    // The file is still returned when something was logged, eg: CSS that failed to parse
+    if logger.is_empty() {
+      return None;
+    }
+    return Some(TransformOutput::untransformed(
+      code.clone(),
+      logger.drain_logs(),
+    ));
   }
 
-  // Generate the final JavaScript/TypeScript code with source maps
-  let codegen = Codegen::new();
-  let codegen = codegen.with_options(CodegenOptions {
-    source_map_path: Some(PathBuf::from(&sourcemap_file_path)),
-    ..CodegenOptions::default()
-  });
-  let result = codegen.build(&program);
+  let (result_code, sourcemap) = match code_output {
+    CodeOutput::Codegen => {
+      // Generate the final JavaScript/TypeScript code with source maps
+      let codegen = Codegen::new();
+      let codegen = codegen.with_options(CodegenOptions {
+        source_map_path: Some(PathBuf::from(&sourcemap_file_path)),
+        ..CodegenOptions::default()
+      });
+      let result = codegen.build(&program);
+
+      // Convert source map to JSON string if available
+      let sourcemap: Option<String> = result.map.map(|map| map.to_json_string());
 
-  let result_code: String = result.code;
+      (result.code, sourcemap)
+    }
+    CodeOutput::Patch => {
+      // Apply the recorded edits to the original code instead of reprinting the program
+      let patched = std::mem::take(&mut output.patches).apply(&code, &sourcemap_file_path);
 
-  // Convert source map to JSON string if available
-  let sourcemap: Option<String> = result.map.map(|map| map.to_json_string());
+      (patched.code, Some(patched.sourcemap))
+    }
+  };
+
+  // Chain the source map of earlier transforms, so the map points to the original sources
+  let sourcemap = match (sourcemap, input_sourcemap) {
+    (Some(sourcemap), Some(input_sourcemap)) => {
+      match compose_source_maps(&sourcemap, &input_sourcemap) {
+        Ok(composed) => Some(composed),
+        Err(err) => {
+          log_warn!(
+            logger,
+            DiagnosticCode::InvalidInputSourcemap,
+            "Failed to apply the input source map: {}",
+            err
+          );
+          Some(sourcemap)
+        }
+      }
+    }
+    (sourcemap, _) => sourcemap,
+  };
 
   // Collect all logs that were accumulated during transformation
-  let logs = get_logger().drain_logs();
+  let logs = logger.drain_logs();
 
   Some(TransformOutput {
     code: result_code,
     sourcemap,
-    css: visitor.extracted_css.join("\n"),
+    css: output.css,
     logs,
-    generated_css_name: visitor.generated_css_name,
+    generated_css_name: output.generated_css_name,
+    virtual_module_id: output.virtual_module_id,
+    css_sourcemap: output.css_sourcemap,
+    components: output.components,
   })
 }
 
+/// Result of [`transform_program`]
+#[derive(Debug, Default)]
+pub struct ProgramTransformOutput {
+  /// Whether styles were found or style properties were removed. The program is left untouched otherwise
+  pub transformed: bool,
+  pub css: String,
+  pub logs: Vec<LogEntry>,
+  pub generated_css_name: Option<String>,
+  /// The module id imported by the program when `css_output` is `virtual`
+  pub virtual_module_id: Option<String>,
+  /// Source map of `css`, when `css_sourcemap` is enabled
+  pub css_sourcemap: Option<String>,
+  /// Styles and class names of every component with styles, in source order
+  pub components: Vec<ComponentMetadata>,
+  /// Source text edits mirroring the AST changes, used for the `patch` code output
+  pub(crate) patches: Patches,
+}
+
+/// Runs the flair transform on a program that was already parsed, eg: inside an oxc based pipeline.
+/// The program is updated in place: styles are extracted, class names are replaced and the CSS import is added.
+/// No code is generated, the caller prints the program as part of its own pipeline.
+///
+/// `scoping` must be built from `program` (eg: with `SemanticBuilder`).
+/// It is not updated, so nodes added by the transform have no symbols or references: the CSS import,
+/// and the `style` bindings of runtime values along with the props parameters they may add.
+/// Rebuild the scoping before running semantic-dependent passes on the program.
+///
+/// `program.source_text` is used for CSS source maps and diagnostics, and the `patch` code output
+/// and `input_sourcemap` options don't apply.
+pub fn transform_program<'a>(
+  allocator: &'a Allocator,
+  program: &mut Program<'a>,
+  scoping: &'a Scoping,
+  file_path: &str,
+  options: TransformOptions,
+  css_preprocessor: Option<&dyn CssPreprocessor>,
+) -> ProgramTransformOutput {
+  let logger = Logger::new();
+  let mut output = run_transform(
+    allocator,
+    program,
+    scoping,
+    file_path.to_string(),
+    options,
+    css_preprocessor,
+    &logger,
+  );
+  output.logs = logger.drain_logs();
+  output
+}
+
+/// Runs the three passes of [`TransformVisitor`] on `program`, logging to `logger`
+fn run_transform<'a>(
+  allocator: &'a Allocator,
+  program: &mut Program<'a>,
+  scoping: &'a Scoping,
+  file_path: String,
+  options: TransformOptions,
+  css_preprocessor: Option<&dyn CssPreprocessor>,
+  logger: &Logger,
+) -> ProgramTransformOutput {
+  // Create the main visitor that will perform the three-pass transformation
+  let mut visitor = TransformVisitor::new(
+    allocator,
+    scoping,
+    file_path,
+    options,
+    css_preprocessor,
+    logger,
+  );
+
+  // Execute the multi-pass transformation on the AST
+  visitor.begin(program);
+
+  if visitor.extracted_css.is_empty() {
+    // Style properties with runtime interpolations are removed all the same
+    if visitor.removed_style_properties {
+      return ProgramTransformOutput {
+        transformed: true,
+        patches: std::mem::take(&mut visitor.patches),
+        ..Default::default()
+      };
+    }
+    return ProgramTransformOutput::default();
+  }
+
+  ProgramTransformOutput {
+    transformed: true,
+    css: visitor.extracted_css.join("\n"),
+    logs: vec![],
+    generated_css_name: visitor.generated_css_name.take(),
+    virtual_module_id: visitor.virtual_module_id.take(),
+    css_sourcemap: visitor.css_sourcemap.take(),
+    components: std::mem::take(&mut visitor.components),
+    patches: std::mem::take(&mut visitor.patches),
+  }
+}
+
+/// A source file passed to [`transform_many`]
+#[cfg_attr(feature = "napi", napi(object))]
+pub struct TransformFile {
+  pub code: String,
+  pub file_path: String,
+}
+
+/// Transforms many files in parallel on the rayon thread pool, with the same options for every file.
+/// The results are returned in the order of `files`, see [`transform`] for the result of each file.
+pub fn transform_many(
+  files: Vec<TransformFile>,
+  options: &TransformOptions,
+  css_preprocessor: Option<&(dyn CssPreprocessor + Sync)>,
+) -> Vec<Option<TransformOutput>> {
+  files
+    .into_par_iter()
+    .map(|file| {
+      transform(
+        file.code,
+        file.file_path,
+        options.clone(),
+        css_preprocessor.map(|preprocessor| preprocessor as &dyn CssPreprocessor),
+      )
+    })
+    .collect()
+}
+
 /// Represents raw CSS data along with its scoping information.
 #[derive(Clone, Debug)]
 pub struct CSSData {
   pub raw_css: String,
   pub is_global: bool,
+  /// Where `raw_css` came from in the source file, used for CSS source maps
+  pub source: CssSourceMapping,
+  /// Interpolations of `raw_css` that are set at runtime, always empty for global CSS
+  pub dynamic_values: Vec<DynamicValue>,
 }
 
 /// Main visitor struct that orchestrates the multi-pass CSS-in-JS transformation.
@@ -205,16 +623,21 @@ pub struct CSSData {
 /// Pass 2: Can't replace myClass yet, but identifies it needs replacement
 /// Pass 3: Replace variable: const myClass = "button_abc123";
 ///
-struct TransformVisitor<'a> {
+struct TransformVisitor<'a, 'b> {
   allocator: &'a Allocator,
   options: TransformOptions,
-  css_preprocessor: &'a Option<NapiFunction<'a, String, String>>,
+  css_preprocessor: Option<&'b dyn CssPreprocessor>,
   /// Symbols for imported "Style" components from flair packages
   style_tag_import_symbols: Vec<SymbolId>,
   /// Symbols for imported "c" / "cn" and other utility functions from flair packages  
   classname_util_symbols: Vec<SymbolId>,
+  /// Symbols of namespace and default imports of flair packages, eg: `F` in `import * as F from "@flairjs/client"`
+  namespace_symbols: Vec<SymbolId>,
   /// Accumulated CSS strings that will be written to the output CSS file
   extracted_css: Vec<String>,
+  /// Source map mappings of each `extracted_css` entry, relative to the entry.
+  /// Empty unless `css_sourcemap` is enabled.
+  extracted_css_mappings: Vec<Vec<Mapping>>,
   /// Maps function/component IDs to their raw CSS content before processing
   /// The id is actually the function's span.start position
   function_id_to_raw_css_mapping: IndexMap<u32, Vec<CSSData>>,
@@ -229,7 +652,7 @@ struct TransformVisitor<'a> {
   /// Current transformation pass
   pass: Pass,
   /// Variable symbol linking for resolving assignments. E.g.,
-  /// ```
+  /// ```jsx
   /// const cl = "button";
   /// const cl2 = cl;
   ///
@@ -240,11 +663,20 @@ struct TransformVisitor<'a> {
   /// Visitor for detecting and processing flair properties in JSX/expressions
   flair_property_visitor: FlairProperty<'a>,
 
-  /// Span start positions of style tags that should be removed from JSX
-  style_tag_symbols: Vec<u32>,
+  /// Span start positions of every style tag of the file, they are removed from the JSX in Pass 2
+  style_tag_spans: HashSet<u32>,
+
+  /// Values of the module-level `const` declarations, used to evaluate interpolations
+  constants: HashMap<SymbolId, StaticValue>,
+
+  /// Span starts of the interpolations that were reported as not static
+  reported_interpolations: HashSet<u32>,
+
+  /// Whether style properties were removed without their styles being extracted, as their
+  /// interpolations aren't static. The code changes even when the file has no other styles.
+  removed_style_properties: bool,
 
   file_path: String,
-  js_env: Option<Env>,
 
   /// Maps function span.start to its class span.start
   /// Used to handle method definitions inside classes
@@ -256,49 +688,123 @@ struct TransformVisitor<'a> {
   parent_class_id: Option<u32>,
 
   generated_css_name: Option<String>,
+
+  virtual_module_id: Option<String>,
+
+  css_sourcemap: Option<String>,
+
+  /// Source text of the program, used to map CSS positions back into the source file
+  source_text: &'a str,
+
+  /// Source text edits mirroring the AST changes, used when `code_output` is `patch`
+  patches: Patches,
+
+  /// Logs of the current transform
+  logger: &'b Logger,
+
+  /// Spans of every function and class, by their span start, used for the component metadata
+  component_spans: HashMap<u32, Span>,
+
+  /// Metadata of every component with styles, filled while processing the CSS
+  components: Vec<ComponentMetadata>,
+
+  /// Module sources and names of the flair APIs
+  api: FlairApi,
+
+  /// Path of the file relative to the project root, names the custom properties of runtime values
+  relative_file_path: String,
+
+  /// Source type of the program, used to parse the code of runtime value bindings
+  source_type: SourceType,
+
+  /// Code of the props of every function and class by their span start, eg: `props` or `this.props`
+  component_props: HashMap<u32, String>,
+
+  /// Runtime values of the scoped styles of every component, set on its elements in Pass 2
+  dynamic_values: HashMap<u32, Vec<DynamicValue>>,
+
+  /// Elements that runtime values were set on, by their span start and the component id
+  bound_elements: HashSet<(u32, u32)>,
+
+  /// Span starts of the runtime values that were set on at least one element
+  bound_values: HashSet<u32>,
 }
 
-impl<'a> TransformVisitor<'a> {
+impl<'a, 'b> TransformVisitor<'a, 'b> {
   fn new(
     allocator: &'a Allocator,
-    ast_builder: AstBuilder<'a>,
     scoping: &'a Scoping,
     file_path: String,
     options: TransformOptions,
-    css_preprocessor: &'a Option<NapiFunction<'a, String, String>>,
-    js_env: Option<Env>,
+    css_preprocessor: Option<&'b dyn CssPreprocessor>,
+    logger: &'b Logger,
   ) -> Self {
     let extracted_css = vec![];
     let identifier_symbol_ids: Vec<SymbolStore> = vec![];
     let css_module_exports: HashMap<u32, HashMap<String, CssModuleExport>> = HashMap::new();
-    let style_tag_symbols: Vec<u32> = vec![];
     let style_tag_import_symbols: Vec<SymbolId> = vec![];
     let classname_util_symbols: Vec<SymbolId> = vec![];
 
     let variable_linking = HashMap::new();
-    let flair_property_visitor = FlairProperty::new(scoping, allocator);
+    let api = FlairApi::from_options(&options.imports);
+    let flair_property_visitor = FlairProperty::new(scoping, allocator, api.clone());
 
     Self {
       allocator,
       css_preprocessor,
       style_tag_import_symbols,
-      style_tag_symbols,
+      style_tag_spans: HashSet::new(),
+      constants: HashMap::new(),
+      reported_interpolations: HashSet::new(),
+      removed_style_properties: false,
       classname_util_symbols,
+      namespace_symbols: vec![],
       extracted_css,
+      extracted_css_mappings: vec![],
       variable_linking,
-      ast_builder,
+      ast_builder: AstBuilder::new(allocator),
       scoping,
       identifier_symbol_ids,
       pass: Pass::First,
       css_module_exports,
       file_path,
       options,
-      js_env,
       function_id_to_raw_css_mapping: IndexMap::new(),
       flair_property_visitor,
       fn_id_to_class_map: HashMap::new(),
       parent_class_id: None,
       generated_css_name: None,
+      virtual_module_id: None,
+      css_sourcemap: None,
+      source_text: "",
+      patches: Patches::default(),
+      logger,
+      component_spans: HashMap::new(),
+      components: vec![],
+      api,
+      relative_file_path: String::new(),
+      source_type: SourceType::default(),
+      component_props: HashMap::new(),
+      dynamic_values: HashMap::new(),
+      bound_elements: HashSet::new(),
+      bound_values: HashSet::new(),
+    }
+  }
+
+  /// Records the CSS of this file in the manifest of `css_out_dir`, `None` removes its entry.
+  fn update_manifest(&self, entry: Option<ManifestEntry>) {
+    if !self.options.manifest.unwrap_or(false)
+      || CssOutput::from_option(self.options.css_output.as_deref()) != CssOutput::File
+    {
+      return;
+    }
+    let Some(css_out_dir) = &self.options.css_out_dir else {
+      return;
+    };
+
+    let source = get_project_relative_path(&self.file_path, self.options.root.as_deref());
+    if let Err(err) = update_manifest(css_out_dir, &source, entry) {
+      log_warn!(self.logger, DiagnosticCode::ManifestError, "{}", err);
     }
   }
 
@@ -313,6 +819,12 @@ impl<'a> TransformVisitor<'a> {
   /// **Pass 3**: Replace variable declarations that contain class names with their hashed equivalents.
   /// This handles cases like `const myClass = "button"` where the variable is used in JSX.
   fn begin(&mut self, program: &mut Program<'a>) {
+    self.source_text = program.source_text;
+    self.source_type = program.source_type;
+    self.relative_file_path =
+      get_project_relative_path(&self.file_path, self.options.root.as_deref());
+    self.constants = collect_constants(program, self.scoping);
+
     // Pass 1: Extract CSS and build CSS module mappings
     self.visit_program(program);
 
@@ -320,91 +832,267 @@ impl<'a> TransformVisitor<'a> {
     self.process_css();
 
     if self.extracted_css.is_empty() {
+      // The file may have had styles in an earlier build
+      self.update_manifest(None);
       // No CSS was extracted, so no further processing is needed
+      if self.removed_style_properties {
+        self.remove_flair_statements(program);
+      }
       return;
     }
 
     // Pass 2: Replace direct class name references and identify variables needing replacement
     self.pass = Pass::Second;
     self.visit_program(program);
+    self.report_unbound_dynamic_values();
 
     // Pass 3: Replace variable declarations that were identified in Pass 2
     self.pass = Pass::Third;
     self.visit_program(program);
 
     // Remove temporary flair statements from the AST
-    Self::remove_flair_statements(program);
+    self.remove_flair_statements(program);
 
-    // Generate a unique hash for this file's CSS output
-    let hash = {
-      let mut hasher = DefaultHasher::new();
-      self.file_path.hash(&mut hasher);
-      hasher.finish()
-    };
+    let css = self.extracted_css.join("\n");
+
+    // Name the CSS file from the naming template, eg: `[contenthash].css` -> `5f1c2e9a0b3d4c6e.css`
+    let css_file_name = get_css_file_name(
+      self.options.css_file_name.as_deref(),
+      &self.file_path,
+      self.options.root.as_deref(),
+      &css,
+    );
 
-    // Create the CSS file path and import statement
-    let current_timestamp = if self.options.append_timestamp_to_css_file.unwrap_or(false) {
+    // Kept for compatibility, prefer a `[contenthash]` naming template for cache busting
+    let css_file_name = if self.options.css_file_name.is_none()
+      && self.options.append_timestamp_to_css_file.unwrap_or(false)
+    {
       let now = SystemTime::now();
       let duration_since_epoch = match now.duration_since(SystemTime::UNIX_EPOCH) {
         Ok(duration) => duration.as_millis(),
         Err(_) => {
-          log_warn!("Failed to get duration since UNIX_EPOCH, using fallback timestamp");
+          log_warn!(
+            self.logger,
+            DiagnosticCode::InvalidTimestamp,
+            "Failed to get duration since UNIX_EPOCH, using fallback timestamp"
+          );
           std::time::Duration::from_secs(0).as_millis()
         }
       };
-      format!("-{}", duration_since_epoch)
+      match css_file_name.strip_suffix(".css") {
+        Some(stem) => format!("{}-{}.css", stem, duration_since_epoch),
+        None => format!("{}-{}", css_file_name, duration_since_epoch),
+      }
     } else {
-      "".to_string()
+      css_file_name
     };
-    let hash_string = format!("{:x}{}.css", hash, current_timestamp);
-    let import_path = format!("@flairjs/client/generated-css/{}", hash_string);
 
-    // Create an import statement for the generated CSS file
-    let import_statement = Statement::from(
-      self.ast_builder.module_declaration_import_declaration(
-        SPAN,
-        None, // No specifiers for side-effect-only import
-        self
-          .ast_builder
-          .string_literal(SPAN, self.allocator.alloc_str(&import_path), None),
-        None,
-        NONE,
-        ImportOrExportKind::Value,
-      ),
-    );
+    let import_path = match CssOutput::from_option(self.options.css_output.as_deref()) {
+      CssOutput::File => {
+        // Write the extracted CSS to a file in the specified output directory
+        let Some(css_out_dir) = &self.options.css_out_dir else {
+          self.logger.log_entry(
+            LogEntry::diagnostic(
+              DiagnosticCode::MissingCssOutDir,
+              LogLevel::Error,
+              "`css_out_dir` is required when `css_output` is \"file\"",
+            )
+            .with_file(&self.file_path)
+            .with_help(
+              "Set `cssOutDir`, or use `cssOutput: 'virtual'` to serve the CSS from the bundler",
+            ),
+          );
+          return;
+        };
+        // The map is written next to the CSS file, so its sources are relative to that directory
+        let css_source_map = self.build_css_source_map(
+          absolute_path(&Path::new(css_out_dir).join(&css_file_name))
+            .parent()
+            .unwrap_or(Path::new(css_out_dir)),
+        );
+        // Set when the map was written, so it can be removed again if the CSS can't be
+        let mut map_path = None;
+        let css = match &css_source_map {
+          Some(css_source_map) => {
+            let map_file_name = format!("{}.map", css_file_name);
+            match write_css_file(css_out_dir, &map_file_name, css_source_map) {
+              Ok(()) => {
+                map_path = Some(Path::new(css_out_dir).join(&map_file_name));
+                let map_url = map_file_name.rsplit('/').next().unwrap_or(&map_file_name);
+                format!("{}\n/*# sourceMappingURL={} */", css, map_url)
+              }
+              Err(err) => {
+                // The CSS is still usable, it just can't point to a map that doesn't exist
+                log_error!(self.logger, DiagnosticCode::CssWriteError, "{}", err);
+                css
+              }
+            }
+          }
+          None => css,
+        };
+        self.css_sourcemap = css_source_map;
 
-    // Write the extracted CSS to a file in the specified output directory
-    let file_path = format!("{}/{}", self.options.css_out_dir, hash_string);
-    match File::create(&file_path) {
-      Ok(mut file) => {
-        if let Err(err) = file.write_all(self.extracted_css.join("\n").as_bytes()) {
-          log_error!(
-            "Failed to write CSS to file: {}, reason: {:#?}",
-            file_path,
-            err
+        if let Err(err) = write_css_file(css_out_dir, &css_file_name, &css) {
+          // Importing a file that doesn't exist would break the module graph of the bundler,
+          // so the import is left out. The CSS is still returned in `css`.
+          if let Some(map_path) = map_path {
+            let _ = std::fs::remove_file(map_path);
+          }
+          self.logger.log_entry(
+            LogEntry::diagnostic(DiagnosticCode::CssWriteError, LogLevel::Error, err)
+              .with_file(&self.file_path)
+              .with_help("The CSS import was not added, the CSS is only returned in `css`"),
           );
+          return;
         }
+
+        let components = self
+          .components
+          .iter()
+          .map(|component| ManifestComponent {
+            name: component.name.clone(),
+            class_names: component.class_names.clone().into_iter().collect(),
+          })
+          .collect();
+        self.update_manifest(Some(ManifestEntry::new(
+          css_file_name.clone(),
+          &css,
+          components,
+        )));
+
+        let css_import = self.options.css_import.as_ref();
+        get_css_import_specifier(
+          CssImportSpecifier::from_option(css_import.and_then(|i| i.specifier.as_deref())),
+          css_import.and_then(|i| i.template.as_deref()),
+          &self.file_path,
+          css_out_dir,
+          &css_file_name,
+        )
       }
+      CssOutput::Virtual => {
+        // Nothing is written to disk, the bundler serves the CSS through the virtual module id
+        let module_id =
+          get_virtual_module_id(self.options.virtual_module_id.as_deref(), &css_file_name);
+        self.virtual_module_id = Some(module_id.clone());
+
+        let project_root = match &self.options.root {
+          Some(root) => absolute_path(Path::new(root)),
+          None => std::env::current_dir().unwrap_or_default(),
+        };
+        self.css_sourcemap = self.build_css_source_map(&project_root);
+
+        module_id
+      }
+    };
+
+    let import_statement = self.build_css_import_statement(&import_path);
+
+    self.generated_css_name = Some(css_file_name);
+    // Insert the CSS import at the top of the transformed file
+    program.body.insert(0, import_statement);
+
+    // When patching, the import goes after the directives (eg: "use client") on the same line,
+    // so that the line numbers of the file don't change
+    let import_code = self.build_css_import_code(&import_path);
+    match (program.directives.last(), &program.hashbang) {
+      (Some(directive), _) => self.patches.insert(directive.span.end, import_code),
+      (None, Some(hashbang)) => self
+        .patches
+        .insert(hashbang.span.end, format!("\n{}", import_code)),
+      (None, None) => self.patches.insert(0, import_code),
+    }
+  }
+
+  /// Builds the source map of the generated CSS if `css_sourcemap` is enabled.
+  /// Sources in the map are relative to `map_dir`.
+  fn build_css_source_map(&self, map_dir: &Path) -> Option<String> {
+    if !self.options.css_sourcemap.unwrap_or(false) {
+      return None;
+    }
+
+    let source_path = absolute_path(Path::new(&self.file_path));
+    match build_css_source_map(
+      &self.extracted_css,
+      &self.extracted_css_mappings,
+      &source_path.to_string_lossy(),
+      self.source_text,
+      map_dir,
+    ) {
+      Ok(css_source_map) => Some(css_source_map),
       Err(err) => {
-        log_error!(
-          "Failed to create file in css_out_dir: {}, reason: {:#?}",
-          self.options.css_out_dir,
-          err
+        log_error!(self.logger, DiagnosticCode::CssSourcemapError, "{}", err);
+        None
+      }
+    }
+  }
+
+  /// Creates the statement that imports the generated CSS.
+  /// Depending on the `css_import.format` option this is either a side-effect-only
+  /// `import "..."` or a `require("...")` call.
+  fn build_css_import_statement(&self, import_path: &str) -> Statement<'a> {
+    let format = ModuleFormat::from_option(
+      self
+        .options
+        .css_import
+        .as_ref()
+        .and_then(|i| i.format.as_deref()),
+    );
+
+    let import_path = self.allocator.alloc_str(import_path);
+
+    match format {
+      ModuleFormat::Esm => Statement::from(self.ast_builder.module_declaration_import_declaration(
+        SPAN,
+        None, // No specifiers for side-effect-only import
+        self.ast_builder.string_literal(SPAN, import_path, None),
+        None,
+        NONE,
+        ImportOrExportKind::Value,
+      )),
+      ModuleFormat::Cjs => {
+        let require_call = self.ast_builder.expression_call(
+          SPAN,
+          self.ast_builder.expression_identifier(SPAN, "require"),
+          NONE,
+          self
+            .ast_builder
+            .vec1(Argument::from(self.ast_builder.expression_string_literal(
+              SPAN,
+              import_path,
+              None,
+            ))),
+          false,
         );
+        self.ast_builder.statement_expression(SPAN, require_call)
       }
     }
+  }
 
-    self.generated_css_name = Some(hash_string);
-    // Insert the CSS import at the top of the transformed file
-    program.body.insert(0, import_statement);
+  /// Source code of the statement that imports the generated CSS, used when patching
+  fn build_css_import_code(&self, import_path: &str) -> String {
+    // A JSON string is a valid JS string literal
+    let import_path = serde_json::to_string(import_path).unwrap_or_default();
+    let format = ModuleFormat::from_option(
+      self
+        .options
+        .css_import
+        .as_ref()
+        .and_then(|i| i.format.as_deref()),
+    );
+
+    match format {
+      ModuleFormat::Esm => format!("import {};", import_path),
+      ModuleFormat::Cjs => format!("require({});", import_path),
+    }
   }
 
   /// Remove __flair_replacement__ statements from the AST
-  fn remove_flair_statements(program: &mut Program<'a>) {
+  fn remove_flair_statements(&mut self, program: &mut Program<'a>) {
     program.body.retain(|stmt| {
       if let Statement::ExpressionStatement(expr_stmt) = stmt {
         if let Expression::StringLiteral(string_lit) = &expr_stmt.expression {
           if string_lit.value == FLAIR_REPLACEMENT {
+            self.patches.remove(expr_stmt.span);
             return false;
           }
         }
@@ -432,159 +1120,376 @@ impl<'a> TransformVisitor<'a> {
         .push(style.to_owned());
     }
 
+    // lightningcss derives the CSS module hashes from the filename, so use the project-relative
+    // path and the component name instead of the absolute path and the component position
+    let relative_file_path = &self.relative_file_path;
+    let mut component_name_counts: HashMap<String, usize> = HashMap::new();
+
+    let parse_css_options = ParseCssOptions {
+      use_theme: self.options.use_theme.unwrap_or(false),
+      theme: &self.options.theme,
+      css_modules: &self.options.css_modules,
+      targets: get_targets(&self.options.targets, &self.file_path, self.logger),
+      minify: self.options.minify.unwrap_or(false),
+    };
+    let css_sourcemap = self.options.css_sourcemap.unwrap_or(false);
+    let source_index = LineIndex::new(self.source_text);
+    let file_path = &self.file_path;
+    let source_text = self.source_text;
+    let logger = self.logger;
+    let css_preprocessor = self.css_preprocessor;
+
     // Process each function's CSS styles
     self
       .function_id_to_raw_css_mapping
       .iter()
-      .enumerate()
-      .for_each(|(index, (fn_id, styles))| {
+      .for_each(|(fn_id, styles)| {
+        // Anonymous or repeated component names are numbered in source order
+        let name = self
+          .flair_property_visitor
+          .get_component_name(*fn_id)
+          .map(str::to_string);
+        let component_name = name.clone().unwrap_or_else(|| "anonymous".to_string());
+        let count = component_name_counts
+          .entry(component_name.clone())
+          .or_insert(0);
+        let component_identity = if *count == 0 {
+          component_name
+        } else {
+          format!("{}-{}", component_name, count)
+        };
+        *count += 1;
+        let css_filename = format!("{}:{}", relative_file_path, component_identity);
+
         // Separate scoped and global CSS for different processing
         let (scoped_css, global_css) = {
-          let mut scoped_css: Option<String> = None;
-          let mut global_css: Option<String> = None;
+          let mut scoped_css: Option<(String, CssSourceMapping)> = None;
+          let mut global_css: Option<(String, CssSourceMapping)> = None;
           styles.iter().for_each(|style| {
-            if style.is_global {
-              global_css
-                .get_or_insert_with(String::new)
-                .push_str(&style.raw_css);
+            let (css, source) = if style.is_global {
+              global_css.get_or_insert_with(Default::default)
             } else {
-              scoped_css
-                .get_or_insert_with(String::new)
-                .push_str(&style.raw_css);
-            }
+              scoped_css.get_or_insert_with(Default::default)
+            };
+            source.extend(&style.source, css.len());
+            css.push_str(&style.raw_css);
           });
 
           (scoped_css, global_css)
         };
 
-        // Apply CSS preprocessing if available.
-        let (preprocessed_scoped_css, preprocessed_global_css) = {
-          if self.js_env.is_some() {
-            if let Some(preprocessor) = &self.css_preprocessor {
-              // Apply preprocessor to scoped CSS, fallback to original on error
-              let scoped_result = scoped_css.as_ref().map(|original| {
-                preprocessor
-                  .call(original.clone())
-                  .unwrap_or(original.clone())
-              });
-
-              // Apply preprocessor to global CSS, fallback to original on error
-              let global_result = global_css.as_ref().map(|original| {
-                preprocessor
-                  .call(original.clone())
-                  .unwrap_or(original.clone())
-              });
-
-              (scoped_result, global_result)
-            } else {
-              // No preprocessor available, use original CSS
-              (scoped_css, global_css)
+        // Apply CSS preprocessing if available, falling back to the original CSS on error.
+        // Positions can't be traced through the preprocessor, so rewritten CSS maps to the start of the styles
+        let preprocess = |(original, source): (String, CssSourceMapping)| {
+          let Some(preprocessor) = css_preprocessor else {
+            return (original, source);
+          };
+          match preprocessor.process(&original) {
+            Ok(css) if css == original => (original, source),
+            Ok(css) => (css, source.collapse()),
+            Err(err) => {
+              log_warn!(
+                logger,
+                DiagnosticCode::CssPreprocessorError,
+                "CSS preprocessor failed for `{}`, using the original CSS: {}",
+                component_identity,
+                err
+              );
+              (original, source)
             }
-          } else {
-            // No JavaScript environment, use original CSS
-            (scoped_css, global_css)
           }
         };
+        let preprocessed_scoped_css = scoped_css.map(preprocess);
+        let preprocessed_global_css = global_css.map(preprocess);
+
+        // Parse the CSS and report its diagnostics. Scoped CSS is parsed with CSS modules enabled
+        // for class name generation, global CSS is parsed as-is
+        let parse = |(css, source): (String, CssSourceMapping), module: bool| {
+          let mut css_map = css_sourcemap.then(|| SourceMap::new("/"));
+          let mut diagnostics = vec![];
+          let res = parse_css(
+            &css,
+            &css_filename,
+            module,
+            &parse_css_options,
+            css_map.as_mut(),
+            &mut diagnostics,
+          );
 
-        let use_theme = self.options.use_theme.unwrap_or(false);
-
-        // Parse scoped CSS with CSS modules enabled for class name generation
-        let parsed_scoped_css: Option<ToCssResult> =
-          preprocessed_scoped_css.clone().and_then(|css| {
-            let res = parse_css(
-              &css,
-              &format!("{}:{}", self.file_path, index),
-              true, // Enable CSS modules for scoped styles
-              use_theme,
-              &self.options.theme,
-            );
-
-            match res {
-              Ok(val) => Some(val),
-              Err(_) => {
-                log_error!(
-                  "Failed to parse CSS in function starting at {}: {:#?}. CSS: {:#?}",
-                  fn_id,
-                  res.err(),
-                  preprocessed_scoped_css
-                );
-                None
-              }
+          let res = match res {
+            Ok(val) => Some(val),
+            Err(diagnostic) => {
+              diagnostics.push(diagnostic);
+              None
             }
-          });
-
-        // Parse global CSS without CSS modules
-        let parsed_global_css: Option<ToCssResult> =
-          preprocessed_global_css.clone().and_then(|css| {
-            let res = parse_css(
+          };
+          for diagnostic in diagnostics {
+            logger.log_entry(LogEntry::from_css_diagnostic(
+              diagnostic,
               &css,
-              &format!("{}:{}", self.file_path, fn_id),
-              false, // Disable CSS modules for global styles
-              use_theme,
-              &self.options.theme,
-            );
-
-            match res {
-              Ok(val) => Some(val),
-              Err(_) => {
-                log_error!(
-                  "Failed to parse CSS in function starting at {}: {:#?}. CSS: {:#?}",
-                  fn_id,
-                  res.err(),
-                  preprocessed_global_css
-                );
-                None
-              }
-            }
-          });
+              &source,
+              file_path,
+              source_text,
+              &source_index,
+            ));
+          }
+
+          res.map(|val| {
+            let mappings = css_map
+              .map(|css_map| remap_css_mappings(&css_map, &css, &source, &source_index))
+              .unwrap_or_default();
+            (val, mappings)
+          })
+        };
+        let parsed_scoped_css: Option<(ParsedCss, Vec<Mapping>)> =
+          preprocessed_scoped_css.and_then(|css| parse(css, true));
+        let parsed_global_css: Option<(ParsedCss, Vec<Mapping>)> =
+          preprocessed_global_css.and_then(|css| parse(css, false));
+
+        let span = self
+          .component_spans
+          .get(fn_id)
+          .copied()
+          .unwrap_or(Span::new(*fn_id, *fn_id));
+        let (line, column) = source_index.position(span.start as usize);
+        let mut component = ComponentMetadata {
+          name,
+          start: span.start,
+          end: span.end,
+          line: line + 1,
+          column: column + 1,
+          class_names: HashMap::new(),
+          scoped_css: None,
+          global_css: None,
+          theme_tokens: vec![],
+        };
 
         // Store CSS module exports for class name replacement in Pass 2
-        if let Some(parsed_scoped_css) = parsed_scoped_css {
+        if let Some((parsed_scoped_css, mappings)) = parsed_scoped_css {
           let empty_exports = HashMap::new();
-          let css_exports = parsed_scoped_css.exports.as_ref().unwrap_or(&empty_exports);
-
+          let css_exports = parsed_scoped_css
+            .result
+            .exports
+            .as_ref()
+            .unwrap_or(&empty_exports);
+
+          component.class_names = css_exports
+            .iter()
+            .map(|(class_name, export)| (class_name.clone(), export.name.clone()))
+            .collect();
           self.css_module_exports.insert(*fn_id, css_exports.clone());
 
-          self.extracted_css.push(parsed_scoped_css.code);
+          // Custom properties are scoped too when `dashed_idents` is enabled
+          let dynamic_values: Vec<DynamicValue> = styles
+            .iter()
+            .flat_map(|style| style.dynamic_values.iter().cloned())
+            .map(|mut value| {
+              if let Some(export) = css_exports.get(&value.name) {
+                value.name = export.name.clone();
+              }
+              value
+            })
+            .collect();
+          if !dynamic_values.is_empty() {
+            self.dynamic_values.insert(*fn_id, dynamic_values);
+          }
+
+          component.scoped_css = Some(parsed_scoped_css.result.code.clone());
+          component.theme_tokens = parsed_scoped_css.theme_tokens;
+          self.extracted_css.push(parsed_scoped_css.result.code);
+          self.extracted_css_mappings.push(mappings);
         }
 
         // Add global CSS directly to the output (no class name mapping needed)
-        if let Some(parsed_global_css) = parsed_global_css {
-          self.extracted_css.push(parsed_global_css.code);
+        if let Some((parsed_global_css, mappings)) = parsed_global_css {
+          component.global_css = Some(parsed_global_css.result.code.clone());
+          for token in parsed_global_css.theme_tokens {
+            if !component.theme_tokens.contains(&token) {
+              component.theme_tokens.push(token);
+            }
+          }
+          self.extracted_css.push(parsed_global_css.result.code);
+          self.extracted_css_mappings.push(mappings);
+        }
+
+        if component.scoped_css.is_some() || component.global_css.is_some() {
+          self.components.push(component);
         }
       });
   }
 
+  /// Reports an interpolation that can't be evaluated at build time, `outcome` tells what was done
+  /// with the styles that contain it.
+  /// Nested functions see the same style tags, so every interpolation is only reported once.
+  fn log_non_static_expression(&mut self, err: NonStaticExpression, outcome: &str) {
+    if !self.reported_interpolations.insert(err.span.start) {
+      return;
+    }
+
+    let source_index = LineIndex::new(self.source_text);
+    self.logger.log_entry(
+      LogEntry::diagnostic(
+        DiagnosticCode::NonStaticInterpolation,
+        LogLevel::Error,
+        format!("Interpolation can't be evaluated at build time: {}", err.reason),
+      )
+      .with_file(&self.file_path)
+      .with_span(
+        self.source_text,
+        &source_index,
+        err.span.start as usize,
+        err.span.end as usize,
+      )
+      .with_help(format!(
+        "Only module-level `const` strings and numbers, arithmetic, string concatenation and properties of `as const` objects can be interpolated. Runtime values are supported in the declaration values of scoped styles. {}",
+        outcome
+      )),
+    );
+  }
+
+  /// Reports the runtime values that no element of their component uses the classes of, their
+  /// custom properties are never set, so the declarations that use them have no effect.
+  fn report_unbound_dynamic_values(&self) {
+    let mut unbound_values: Vec<&DynamicValue> = self
+      .dynamic_values
+      .values()
+      .flatten()
+      .filter(|value| !self.bound_values.contains(&value.span.start))
+      .collect();
+    if unbound_values.is_empty() {
+      return;
+    }
+    unbound_values.sort_by_key(|value| value.span.start);
+
+    let source_index = LineIndex::new(self.source_text);
+    for value in unbound_values {
+      let class_names = value
+        .class_names
+        .iter()
+        .map(|class_name| format!("`.{}`", class_name))
+        .collect::<Vec<_>>()
+        .join(", ");
+      self.logger.log_entry(
+        LogEntry::diagnostic(
+          DiagnosticCode::UnboundRuntimeValue,
+          LogLevel::Warn,
+          format!(
+            "Runtime value is not set on any element, no element of the component has the class {}",
+            class_names
+          ),
+        )
+        .with_file(&self.file_path)
+        .with_span(
+          self.source_text,
+          &source_index,
+          value.span.start as usize,
+          value.span.end as usize,
+        )
+        .with_help(
+          "Runtime values are set on the elements whose class name attributes contain the class as a string, eg: `className=\"btn\"` or `cn(\"btn\", active && \"active\")`. Class names passed through variables or props are not matched.",
+        ),
+      );
+    }
+  }
+
+  /// Removes the registered style tags from the children of a JSX element or fragment
+  fn remove_style_tags(&mut self, children: &mut ArenaVec<'a, JSXChild<'a>>) {
+    children.retain(|child| {
+      if let JSXChild::Element(element) = child {
+        if self.style_tag_spans.contains(&element.span.start) {
+          self.patches.remove(element.span);
+          return false;
+        }
+      }
+      true
+    });
+  }
+
   /// Processes function bodies differently based on the current transformation pass.
   /// This method coordinates the different phases of transformation for each function.
   fn process_function_body(&mut self, body: &mut FunctionBody<'a>, fn_start: u32) {
     match self.pass {
       Pass::First => {
         // Detect and collect style tag information and CSS content
-        let mut style_detector = StyleDetector::new(self.scoping, &self.style_tag_import_symbols);
+        let evaluator = StaticEvaluator::new(self.scoping, &self.constants);
+        let mut style_detector = StyleDetector::new(
+          self.scoping,
+          &self.style_tag_import_symbols,
+          &self.namespace_symbols,
+          &self.api.style,
+          &evaluator,
+          &self.relative_file_path,
+        );
         style_detector.visit_function_body(body);
 
+        let errors = std::mem::take(&mut style_detector.errors);
+        let style_tags = style_detector.into_style_tags();
+        for err in errors {
+          self.log_non_static_expression(
+            err,
+            "The style element was not extracted and was left in the code.",
+          );
+        }
+
         if let Some(class_id) = self.parent_class_id {
           self.fn_id_to_class_map.insert(fn_start, class_id);
         }
 
-        // If style tags were detected, store the CSS and symbol information
-        if style_detector.has_style {
-          self.style_tag_symbols = style_detector.get_style_tag_symbol_ids();
-
-          // If this function is inside a class, map it to the parent class for CSS scope inheritance
-          if let Some(class_id) = self.parent_class_id {
-            self
-              .function_id_to_raw_css_mapping
-              .insert(class_id, style_detector.css);
-          } else {
-            self
-              .function_id_to_raw_css_mapping
-              .insert(fn_start, style_detector.css);
+        // The detector also sees the style tags of nested functions, which were already
+        // registered by the outer function. Each style tag belongs to the outermost one.
+        let style_tags: Vec<(u32, CSSData)> = style_tags
+          .into_iter()
+          .filter(|(span_start, _)| !self.style_tag_spans.contains(span_start))
+          .collect();
+
+        // If new style tags were detected, store the CSS and register the tags for removal
+        if !style_tags.is_empty() {
+          // If this function is inside a class, map it to the parent class for CSS scope inheritance.
+          // The CSS of every method is accumulated under the class.
+          let css_id = self.parent_class_id.unwrap_or(fn_start);
+          let css = self
+            .function_id_to_raw_css_mapping
+            .entry(css_id)
+            .or_default();
+
+          for (span_start, css_data) in style_tags {
+            self.style_tag_spans.insert(span_start);
+            css.push(css_data);
           }
         }
       }
       Pass::Second => {
+        let class_name_list = self
+          .options
+          .class_name_list
+          .clone()
+          .unwrap_or(vec!["className".to_string(), "class".to_string()]);
+
+        // Set the runtime values of the component on its elements, before their classes are replaced
+        let css_id = *self.fn_id_to_class_map.get(&fn_start).unwrap_or(&fn_start);
+        if let Some(values) = self.dynamic_values.get(&css_id) {
+          let mut binder = DynamicStyleBinder {
+            allocator: self.allocator,
+            ast_builder: self.ast_builder,
+            source_text: self.source_text,
+            source_type: self.source_type,
+            values,
+            props_code: self
+              .component_props
+              .get(&css_id)
+              .map(String::as_str)
+              .unwrap_or("{}"),
+            class_name_list: class_name_list.clone(),
+            css_id,
+            bound_elements: &mut self.bound_elements,
+            bound_values: &mut self.bound_values,
+            patches: Patches::default(),
+          };
+          binder.visit_function_body(body);
+          let patches = binder.patches;
+          self.patches.extend(patches);
+        }
+
         // Replace direct className references and identify variables that need replacement in Pass 3
         let mut classname_replacer = ClassNameReplacer {
           allocator: self.allocator,
@@ -594,18 +1499,18 @@ impl<'a> TransformVisitor<'a> {
           identifier_symbol_ids: self.identifier_symbol_ids.clone(),
           fn_id: fn_start,
           classname_util_symbols: self.classname_util_symbols.clone(),
+          namespace_symbols: self.namespace_symbols.clone(),
+          class_name_utils: self.api.class_name_utils.clone(),
           variable_linking: self.variable_linking.clone(),
-          class_name_list: self
-            .options
-            .class_name_list
-            .clone()
-            .unwrap_or(vec!["className".to_string(), "class".to_string()]),
+          class_name_list,
+          patches: Patches::default(),
         };
 
         classname_replacer.visit_function_body(body);
 
         // Update our tracking of which identifiers need to be processed in Pass 3
         self.identifier_symbol_ids = classname_replacer.get_identifier_symbol_ids().to_vec();
+        self.patches.extend(classname_replacer.patches);
       }
       Pass::Third => {
         // Pass 3 is handled at the variable declaration level, not at the function body level
@@ -628,14 +1533,42 @@ impl<'a> TransformVisitor<'a> {
     }
   }
 
-  fn get_import_symbol(&self, import_specifier: &ImportSpecifier, name: &str) -> Option<SymbolId> {
+  /// Makes every prop available to the function values of a component, see [`bind_props`]
+  fn bind_component_props(&mut self, params: &mut FormalParameters<'a>, fn_start: u32) {
+    let has_function_values = self
+      .dynamic_values
+      .get(&fn_start)
+      .is_some_and(|values| values.iter().any(|value| value.is_function));
+    if !has_function_values {
+      return;
+    }
+
+    bind_props(
+      self.ast_builder,
+      params,
+      self.source_text,
+      &mut self.patches,
+    );
+    self
+      .component_props
+      .insert(fn_start, get_props_code(params));
+  }
+
+  fn get_import_symbol(
+    &self,
+    import_specifier: &ImportSpecifier,
+    names: &[String],
+  ) -> Option<SymbolId> {
     if let ModuleExportName::IdentifierName(identifier) = &import_specifier.imported {
-      if identifier.name == name {
+      if names.iter().any(|name| identifier.name == name.as_str()) {
         return Some(import_specifier.local.symbol_id());
       }
     }
 
-    if import_specifier.local.name == name {
+    if names
+      .iter()
+      .any(|name| import_specifier.local.name == name.as_str())
+    {
       return Some(import_specifier.local.symbol_id());
     }
 
@@ -643,29 +1576,43 @@ impl<'a> TransformVisitor<'a> {
   }
 }
 
-impl<'a> VisitMut<'a> for TransformVisitor<'a> {
+impl<'a> VisitMut<'a> for TransformVisitor<'a, '_> {
   /// Processes import declarations to identify flair-related imports.
-  /// Tracks symbols for "Style" components and "c" utility functions from flair packages.
+  /// Tracks symbols for "Style" components and "c" utility functions from flair packages,
+  /// and namespace / default imports of flair packages.
   fn visit_import_declaration(&mut self, it: &mut ImportDeclaration<'a>) {
-    if it.source.value.as_str().starts_with(IMPORT_PATH) && self.pass == Pass::First {
+    if self.api.is_source(it.source.value.as_str()) && self.pass == Pass::First {
       let specifiers = it.specifiers.as_ref();
       if let Some(specifiers) = specifiers {
         specifiers.iter().for_each(|specifier| {
           if let ImportDeclarationSpecifier::ImportSpecifier(import_specifier) = specifier {
             // Track the "Style" component import for style tag detection
-            let style_import_symbol = self.get_import_symbol(import_specifier, "Style");
+            let style_import_symbol = self.get_import_symbol(import_specifier, &self.api.style);
             if let Some(symbol_id) = style_import_symbol {
               self.style_tag_import_symbols.push(symbol_id);
             }
-            // Track the "c" utility function import for className processing
-            let c_import_symbol = self.get_import_symbol(import_specifier, "c");
-            let cn_import_symbol = self.get_import_symbol(import_specifier, "cn");
-            if let Some(symbol_id) = c_import_symbol {
+            // Track the "c" / "cn" utility function imports for className processing
+            let class_name_util_symbol =
+              self.get_import_symbol(import_specifier, &self.api.class_name_utils);
+            if let Some(symbol_id) = class_name_util_symbol {
               self.classname_util_symbols.push(symbol_id);
             }
-            if let Some(symbol_id) = cn_import_symbol {
-              self.classname_util_symbols.push(symbol_id);
+          }
+
+          // Track namespace and default imports, their members are resolved where they are used
+          // eg: `import * as F from "@flairjs/client/react"` for `<F.Style>` and `F.cn(...)`
+          let namespace_symbol = match specifier {
+            ImportDeclarationSpecifier::ImportNamespaceSpecifier(namespace) => {
+              Some(namespace.local.symbol_id())
+            }
+            ImportDeclarationSpecifier::ImportDefaultSpecifier(default) => {
+              Some(default.local.symbol_id())
             }
+            ImportDeclarationSpecifier::ImportSpecifier(_) => None,
+          };
+          if let Some(symbol_id) = namespace_symbol {
+            self.namespace_symbols.push(symbol_id);
+            self.flair_property_visitor.add_namespace_symbol(symbol_id);
           }
         });
       }
@@ -709,7 +1656,10 @@ impl<'a> VisitMut<'a> for TransformVisitor<'a> {
         // Example: const className = "button" -> const className = "button_abc123"
         it.declarations.iter_mut().for_each(|decl| {
           if let BindingPatternKind::BindingIdentifier(binding_identifier) = &decl.id.kind {
-            let symbol_id = binding_identifier.symbol_id();
+            // Declarations generated by the transform have no symbol
+            let Some(symbol_id) = binding_identifier.symbol_id.get() else {
+              return;
+            };
 
             // Check if this variable was identified in Pass 2 as needing CSS class name replacement
             let symbol_store_item = self
@@ -734,17 +1684,21 @@ impl<'a> VisitMut<'a> for TransformVisitor<'a> {
                 identifier_symbol_ids: vec![],
                 fn_id: decl.span.start,
                 classname_util_symbols: self.classname_util_symbols.clone(),
+                namespace_symbols: self.namespace_symbols.clone(),
+                class_name_utils: self.api.class_name_utils.clone(),
                 variable_linking: self.variable_linking.clone(),
                 class_name_list: self
                   .options
                   .class_name_list
                   .clone()
                   .unwrap_or(vec!["className".to_string(), "class".to_string()]),
+                patches: Patches::default(),
               };
 
               if decl.init.is_some() {
                 classname_replacer.update_expression(decl.init.as_mut());
               }
+              self.patches.extend(classname_replacer.patches);
             }
           }
         });
@@ -758,36 +1712,67 @@ impl<'a> VisitMut<'a> for TransformVisitor<'a> {
   fn visit_expression(&mut self, it: &mut Expression<'a>) {
     if self.pass == Pass::First {
       // Collect flair property information during the first pass
-      self.flair_property_visitor.visit_expression(it);
+      let evaluator = StaticEvaluator::new(self.scoping, &self.constants);
+      if let Err(err) =
+        self
+          .flair_property_visitor
+          .visit_expression(it, &evaluator, &self.relative_file_path)
+      {
+        self.removed_style_properties = true;
+        self.log_non_static_expression(
+          err,
+          "These styles were not extracted, and the style property assignment was removed.",
+        );
+      }
     } else if self.pass == Pass::Second {
-      // Remove Style components from JSX during the second pass
+      // Style components in expression position, eg: `cond && <Style>` or `return <Style>`,
+      // are replaced with `null`. Style components that are JSX children are removed in
+      // `visit_jsx_element` / `visit_jsx_fragment`.
       if let Expression::JSXElement(jsx) = it {
-        jsx.children.retain_mut(|child| {
-          if let JSXChild::Element(element) = child {
-            if self.style_tag_symbols.contains(&element.span.start) {
-              return false;
-            }
-            return true;
-          }
-          true
-        });
-      } else if let Expression::JSXFragment(jsx) = it {
-        jsx.children.retain_mut(|child| {
-          if let JSXChild::Element(element) = child {
-            // Remove elements that match detected Style component symbols
-            if self.style_tag_symbols.contains(&element.span.start) {
-              return false;
-            }
-            return true;
-          }
-          true
-        });
+        if self.style_tag_spans.contains(&jsx.span.start) {
+          let span = jsx.span;
+          // Keep the line breaks of the element so the following code keeps its line numbers
+          let line_breaks = self.source_text[span.start as usize..span.end as usize]
+            .matches('\n')
+            .count();
+          self
+            .patches
+            .replace(span, format!("null{}", "\n".repeat(line_breaks)));
+          *it = self.ast_builder.expression_null_literal(span);
+        }
       }
     }
     walk_mut::walk_expression(self, it);
   }
 
+  fn visit_jsx_element(&mut self, it: &mut JSXElement<'a>) {
+    if self.pass == Pass::Second {
+      self.remove_style_tags(&mut it.children);
+    }
+    walk_mut::walk_jsx_element(self, it);
+  }
+
+  fn visit_jsx_fragment(&mut self, it: &mut JSXFragment<'a>) {
+    if self.pass == Pass::Second {
+      self.remove_style_tags(&mut it.children);
+    }
+    walk_mut::walk_jsx_fragment(self, it);
+  }
+
   fn visit_arrow_function_expression(&mut self, it: &mut ArrowFunctionExpression<'a>) {
+    // Functions generated by the transform, eg: in the runtime values of styles, have no span or
+    // semantic information, and are never components
+    if it.span.is_unspanned() {
+      return;
+    }
+    if self.pass == Pass::First {
+      self.component_spans.insert(it.span.start, it.span);
+      self
+        .component_props
+        .insert(it.span.start, get_props_code(&it.params));
+    } else if self.pass == Pass::Second {
+      self.bind_component_props(&mut it.params, it.span.start);
+    }
     let body = it.body.as_mut();
     self.process_function_body(body, it.span.start);
 
@@ -795,6 +1780,20 @@ impl<'a> VisitMut<'a> for TransformVisitor<'a> {
   }
 
   fn visit_function(&mut self, function: &mut Function<'a>, flags: ScopeFlags) {
+    // Generated by the transform, see `visit_arrow_function_expression`
+    if function.span.is_unspanned() {
+      return;
+    }
+    if self.pass == Pass::First {
+      self
+        .component_spans
+        .insert(function.span.start, function.span);
+      self
+        .component_props
+        .insert(function.span.start, get_props_code(&function.params));
+    } else if self.pass == Pass::Second {
+      self.bind_component_props(&mut function.params, function.span.start);
+    }
     let body = match function.body.as_mut() {
       Some(body) => body,
       None => {
@@ -814,6 +1813,10 @@ impl<'a> VisitMut<'a> for TransformVisitor<'a> {
     if self.pass == Pass::First {
       // Process flair properties in the class
       self.flair_property_visitor.visit_class(it);
+      self.component_spans.insert(it.span.start, it.span);
+      self
+        .component_props
+        .insert(it.span.start, "this.props".to_string());
 
       // Set the current class as parent context for any methods within it
       // This allows methods to inherit CSS scope from their containing class
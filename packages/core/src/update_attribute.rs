@@ -18,9 +18,13 @@ use oxc::{
   ast::ast::JSXAttribute,
   ast_visit::{walk_mut, VisitMut},
   semantic::{Scoping, SymbolId},
+  span::Span,
 };
 use regex::Regex;
 
+use crate::imports::is_namespace_member;
+use crate::patch::Patches;
+
 /// Stores a symbol ID along with its associated function ID for tracking
 /// variable references that need to be processed in a later pass
 /// During the second pass of transformation, when we encounter variables that
@@ -63,6 +67,10 @@ pub struct ClassNameReplacer<'a> {
   pub fn_id: u32,
   /// Symbol IDs of utility functions that handle class names (e.g., c, cn) imported from client library
   pub classname_util_symbols: Vec<SymbolId>,
+  /// Namespace and default imports of flair modules, eg: `F` for `F.cn(...)`
+  pub namespace_symbols: Vec<SymbolId>,
+  /// Names of the class name utilities, used for members of `namespace_symbols`
+  pub class_name_utils: Vec<String>,
   /// Maps variable symbols to their linked/aliased symbols for resolution
   /// Currently handles simple cases like: const a = b; b = "className"
   /// Future enhancement: support all kinds of variable assignments including
@@ -71,6 +79,8 @@ pub struct ClassNameReplacer<'a> {
   /// List of attribute names to process (e.g., ["className", "class"])
   /// Supports regex patterns wrapped in forward slashes
   pub class_name_list: Vec<String>,
+  /// Source text edits for every updated class name, used when the code is patched instead of reprinted
+  pub patches: Patches,
 }
 
 impl<'a> ClassNameReplacer<'a> {
@@ -82,24 +92,8 @@ impl<'a> ClassNameReplacer<'a> {
     &self.identifier_symbol_ids
   }
 
-  /// Checks if a given attribute name should be processed for class name transformation
-  /// Supports both exact string matching and regex patterns (wrapped in forward slashes)
-  /// Example: "className" matches exactly, "/class.*/" matches "className", "class", etc.
   fn is_classname_in_list(&self, class_name: &str) -> bool {
-    let item = self.class_name_list.iter().find(|item| {
-      // Check if the item is a regex pattern
-      if item.starts_with("/") && item.ends_with("/") {
-        // Extract the regex pattern by removing the surrounding slashes
-        let pattern = &item[1..item.len() - 1];
-        if let Ok(re) = Regex::new(pattern) {
-          return re.is_match(class_name);
-        }
-      }
-      // Fallback to exact string matching
-      *item == class_name
-    });
-
-    item.is_some()
+    is_class_name_attribute(&self.class_name_list, class_name)
   }
 
   /// Transforms class names from original to CSS module equivalents
@@ -124,6 +118,21 @@ impl<'a> ClassNameReplacer<'a> {
   /// Transforms the class names and creates a new atom in the allocator
   fn update_string_expression(&mut self, string_value: &mut OxcBox<'a, StringLiteral<'a>>) {
     let updated_class_names_str = self.get_updated_classname(&string_value.value);
+    if updated_class_names_str != string_value.value.as_str() {
+      let span = string_value.span;
+      if string_value.raw.is_some() && span.size() >= 2 {
+        // Keep the original quotes and only replace the content
+        self.patches.replace(
+          Span::new(span.start + 1, span.end - 1),
+          updated_class_names_str.clone(),
+        );
+      } else {
+        // Literals without raw text were created from identifier keys, so the whole span is replaced
+        self
+          .patches
+          .replace(span, format!("\"{}\"", updated_class_names_str));
+      }
+    }
     // Create a new atom in the allocator for the updated string
     let atom = self
       .ast_builder
@@ -147,11 +156,24 @@ impl<'a> ClassNameReplacer<'a> {
   fn update_object_expression(&mut self, object_expression: &mut OxcBox<'a, ObjectExpression<'a>>) {
     for prop in &mut object_expression.properties {
       if let ObjectPropertyKind::ObjectProperty(property) = prop {
+        let shorthand = property.shorthand;
         // Only process string literal keys, not computed properties or identifiers
         if let PropertyKey::StringLiteral(string_key) = &mut property.key {
           self.update_string_expression(string_key);
         } else if let PropertyKey::StaticIdentifier(static_identifier) = &mut property.key {
           let updated_class_names_str = self.get_updated_classname(&static_identifier.name);
+          if updated_class_names_str != static_identifier.name.as_str() {
+            // Shorthand properties (`{ active }`) need the value to stay after the new key
+            let key_text = if shorthand {
+              format!(
+                "\"{}\": {}",
+                updated_class_names_str, static_identifier.name
+              )
+            } else {
+              format!("\"{}\"", updated_class_names_str)
+            };
+            self.patches.replace(static_identifier.span, key_text);
+          }
           // Switch to a string literal key so hashed names remain valid property names
           let string_literal_key = self.ast_builder.string_literal(
             static_identifier.span,
@@ -273,11 +295,12 @@ impl<'a> ClassNameReplacer<'a> {
   /// During the second pass of transformation, we can't directly update variable values
   /// that reference class names, so we collect them here to be processed in the third pass
   fn update_identifier_expression(&mut self, identifier_expression: &mut IdentifierReference<'a>) {
-    // Get the semantic reference for this identifier
-    let reference = self
-      .scoping
-      .get_reference(identifier_expression.reference_id());
-    let symbol_id = reference.symbol_id();
+    // Get the semantic reference for this identifier.
+    // Nodes generated by the transform, eg: the runtime values of styles, have none
+    let symbol_id = identifier_expression
+      .reference_id
+      .get()
+      .and_then(|reference_id| self.scoping.get_reference(reference_id).symbol_id());
 
     if let Some(symbol_id) = symbol_id {
       // Resolve any variable linking to get the final symbol
@@ -299,6 +322,11 @@ impl<'a> ClassNameReplacer<'a> {
     // Update the static string parts of the template literal
     template_expression.quasis.iter_mut().for_each(|elem| {
       let updated_class_names_str = self.get_updated_classname(&elem.value.raw);
+      if updated_class_names_str != elem.value.raw.as_str() {
+        self
+          .patches
+          .replace(elem.span, updated_class_names_str.clone());
+      }
       let atom = self
         .ast_builder
         .atom(self.allocator.alloc_str(&updated_class_names_str));
@@ -357,21 +385,28 @@ impl<'a> VisitMut<'a> for ClassNameReplacer<'a> {
   /// Visits call expressions to handle class name utility functions
   /// Special handling for functions like c, cn, etc.
   fn visit_call_expression(&mut self, it: &mut CallExpression<'a>) {
-    // Check if the callee is an identifier (function name)
-    if let Expression::Identifier(identifier_calle) = &it.callee {
-      // Resolve the function's symbol to check if it's a class name utility
-      let callee_ref = self.scoping.get_reference(identifier_calle.reference_id());
-      let callee_symbol_id = callee_ref.symbol_id();
-
-      if let Some(callee_symbol_id) = callee_symbol_id {
-        // If this function is registered as a class name utility (like c, cn)
-        if self.classname_util_symbols.contains(&callee_symbol_id) {
-          // Process all arguments to the utility function
-          it.arguments.iter_mut().for_each(|arg| {
-            self.update_expression(arg.as_expression_mut());
-          });
-        }
-      }
+    let is_classname_util = match &it.callee {
+      // Resolve the function's symbol to check if it's a registered class name utility (like c, cn)
+      // Generated callees have no reference, eg: `fmt` in the runtime value `${fmt(tone)}`
+      Expression::Identifier(identifier_calle) => identifier_calle
+        .reference_id
+        .get()
+        .and_then(|reference_id| self.scoping.get_reference(reference_id).symbol_id())
+        .is_some_and(|callee_symbol_id| self.classname_util_symbols.contains(&callee_symbol_id)),
+      // `F.cn(...)`, where `F` is a namespace or default import of a flair module
+      callee => is_namespace_member(
+        self.scoping,
+        &self.namespace_symbols,
+        callee,
+        &self.class_name_utils,
+      ),
+    };
+
+    if is_classname_util {
+      // Process all arguments to the utility function
+      it.arguments.iter_mut().for_each(|arg| {
+        self.update_expression(arg.as_expression_mut());
+      });
     }
     walk_mut::walk_call_expression(self, it);
   }
@@ -435,3 +470,23 @@ impl<'a> VisitMut<'a> for ClassNameReplacer<'a> {
     walk_mut::walk_jsx_attribute(self, it);
   }
 }
+
+/// Checks if a given attribute name should be processed for class name transformation
+/// Supports both exact string matching and regex patterns (wrapped in forward slashes)
+/// Example: "className" matches exactly, "/class.*/" matches "className", "class", etc.
+pub fn is_class_name_attribute(class_name_list: &[String], class_name: &str) -> bool {
+  let item = class_name_list.iter().find(|item| {
+    // Check if the item is a regex pattern
+    if item.starts_with("/") && item.ends_with("/") {
+      // Extract the regex pattern by removing the surrounding slashes
+      let pattern = &item[1..item.len() - 1];
+      if let Ok(re) = Regex::new(pattern) {
+        return re.is_match(class_name);
+      }
+    }
+    // Fallback to exact string matching
+    *item == class_name
+  });
+
+  item.is_some()
+}
@@ -0,0 +1,222 @@
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+/// Creates an empty project directory for a test
+fn project(name: &str) -> PathBuf {
+  let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+    .join("cli")
+    .join(name);
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
+fn write(dir: &Path, path: &str, content: &str) {
+  let path = dir.join(path);
+  fs::create_dir_all(path.parent().unwrap()).unwrap();
+  fs::write(path, content).unwrap();
+}
+
+fn flair(dir: &Path, args: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_flair"))
+    .current_dir(dir)
+    .args(args)
+    .output()
+    .unwrap()
+}
+
+fn component(name: &str) -> String {
+  format!(
+    "export const {name} = () => <button className=\"button\">Click</button>;\n{name}.flair = `.button {{ color: red; }}`;\n"
+  )
+}
+
+#[test]
+fn writes_the_code_and_css_of_every_file() {
+  let dir = project("output");
+  write(&dir, "src/a/index.tsx", &component("Tsx"));
+  write(&dir, "src/a/index.jsx", &component("Jsx"));
+  write(&dir, "src/plain.ts", "export const a = 1;\n");
+  write(
+    &dir,
+    "flair.config.json",
+    r#"{ "cssModules": { "pattern": "flair-[local]" }, "targets": { "browserslist": ["chrome 120"] } }"#,
+  );
+
+  let output = flair(&dir, &["src", "--out-dir", "dist", "--emit", "source"]);
+  assert!(output.status.success(), "{:?}", output);
+
+  // Files with the same name get their own CSS
+  for file in ["index.tsx", "index.jsx"] {
+    let code = fs::read_to_string(dir.join("dist/src/a").join(file)).unwrap();
+    assert!(
+      code.contains(&format!("import \"./{}.css\"", file)),
+      "{}",
+      code
+    );
+    assert!(code.contains("className=\"flair-button\""), "{}", code);
+
+    let css = fs::read_to_string(dir.join("dist/src/a").join(format!("{}.css", file))).unwrap();
+    assert!(css.contains(".flair-button"), "{}", css);
+  }
+  assert_eq!(
+    fs::read_to_string(dir.join("dist/src/plain.ts")).unwrap(),
+    "export const a = 1;\n"
+  );
+}
+
+#[test]
+fn reads_toml_configs() {
+  let dir = project("toml");
+  write(&dir, "src/Button.tsx", &component("Button"));
+  write(
+    &dir,
+    "flair.config.toml",
+    "[cssModules]\npattern = \"toml-[local]\"\n",
+  );
+
+  let output = flair(&dir, &["src/*.tsx"]);
+  assert!(output.status.success(), "{:?}", output);
+  let css = fs::read_to_string(dir.join("dist/src/Button.tsx.css")).unwrap();
+  assert!(css.contains(".toml-button"), "{}", css);
+}
+
+#[test]
+fn fails_on_unknown_config_options() {
+  let dir = project("unknown-option");
+  write(&dir, "src/Button.tsx", &component("Button"));
+  write(
+    &dir,
+    "flair.config.json",
+    r#"{ "cssModules": { "patern": "[local]" } }"#,
+  );
+
+  let output = flair(&dir, &["src"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("patern"));
+}
+
+#[test]
+fn fails_on_inputs_without_files() {
+  let dir = project("missing-input");
+  write(&dir, "src/Button.tsx", &component("Button"));
+
+  let output = flair(&dir, &["srcc"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("Input not found: srcc"));
+
+  let output = flair(&dir, &["src/**/*.vue"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("src/**/*.vue"));
+  assert!(!dir.join("dist").exists());
+}
+
+#[test]
+fn compiles_typescript_and_jsx_to_js() {
+  let dir = project("emit-js");
+  write(
+    &dir,
+    "src/Button.tsx",
+    "import { Label } from \"./Label.tsx\";\nexport const Button = ({ label }: { label: string }) => <button className=\"button\"><Label text={label} /></button>;\nButton.flair = `.button { color: red; }`;\n",
+  );
+  write(
+    &dir,
+    "src/Label.tsx",
+    "export const Label = ({ text }: { text: string }) => <span>{text}</span>;\n",
+  );
+
+  let output = flair(&dir, &["src"]);
+  assert!(output.status.success(), "{:?}", output);
+
+  let code = fs::read_to_string(dir.join("dist/src/Button.js")).unwrap();
+  assert!(code.contains("import \"./Button.tsx.css\""), "{}", code);
+  assert!(code.contains("from \"./Label.js\""), "{}", code);
+  assert!(code.contains("react/jsx-runtime"), "{}", code);
+  assert!(!code.contains("label: string"), "{}", code);
+  assert!(!code.contains("<button"), "{}", code);
+  assert!(dir.join("dist/src/Button.tsx.css").is_file());
+
+  let code = fs::read_to_string(dir.join("dist/src/Label.js")).unwrap();
+  assert!(!code.contains("text: string"), "{}", code);
+  assert!(!dir.join("dist/src/Button.tsx").exists());
+}
+
+#[test]
+fn fails_when_sources_are_compiled_to_the_same_file() {
+  let dir = project("emit-js-conflict");
+  write(&dir, "src/index.tsx", &component("Tsx"));
+  write(&dir, "src/index.jsx", &component("Jsx"));
+
+  let output = flair(&dir, &["src"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("--emit source"));
+}
+
+/// Stops the watching process, also when an assertion fails
+struct KillOnDrop(Child);
+
+impl Drop for KillOnDrop {
+  fn drop(&mut self) {
+    let _ = self.0.kill();
+    let _ = self.0.wait();
+  }
+}
+
+/// Waits until `condition` holds, failing after a few seconds
+fn wait_for(mut condition: impl FnMut() -> bool, what: &str) {
+  let start = Instant::now();
+  while !condition() {
+    assert!(
+      start.elapsed() < Duration::from_secs(10),
+      "timed out waiting for {}",
+      what
+    );
+    std::thread::sleep(Duration::from_millis(50));
+  }
+}
+
+#[test]
+fn watch_removes_the_output_of_deleted_sources_and_styles() {
+  let dir = project("watch-remove");
+  write(&dir, "src/Button.tsx", &component("Button"));
+  write(&dir, "src/Link.tsx", &component("Link"));
+
+  let mut child = KillOnDrop(
+    Command::new(env!("CARGO_BIN_EXE_flair"))
+      .current_dir(&dir)
+      .args(["src", "--watch"])
+      .stderr(Stdio::piped())
+      .spawn()
+      .unwrap(),
+  );
+  let mut stderr = BufReader::new(child.0.stderr.take().unwrap());
+  let mut line = String::new();
+  while !line.contains("Watching for changes") {
+    line.clear();
+    assert!(stderr.read_line(&mut line).unwrap() > 0, "flair exited");
+  }
+
+  assert!(dir.join("dist/src/Button.tsx.css").is_file());
+  fs::remove_file(dir.join("src/Button.tsx")).unwrap();
+  wait_for(
+    || !dir.join("dist/src/Button.js").exists() && !dir.join("dist/src/Button.tsx.css").exists(),
+    "the output of the deleted source to be removed",
+  );
+
+  // A source without styles no longer has CSS
+  write(
+    &dir,
+    "src/Link.tsx",
+    "export const Link = () => <a>Link</a>;\n",
+  );
+  wait_for(
+    || !dir.join("dist/src/Link.tsx.css").exists(),
+    "the CSS of the source without styles to be removed",
+  );
+  assert!(dir.join("dist/src/Link.js").is_file());
+}
@@ -0,0 +1,32 @@
+use flair_core::transform::{transform, TransformOptions, TransformOutput};
+
+fn transform_card(component: &str) -> TransformOutput {
+  let code = format!(
+    "import {{ forwardRef }} from \"react\";\nconst Card = forwardRef({});\nCard.flair = `.card {{ color: red; }}`;\n",
+    component
+  );
+  let options = TransformOptions {
+    css_output: Some("virtual".to_string()),
+    ..Default::default()
+  };
+  transform(code, "card.tsx".to_string(), options, None).expect("transform output")
+}
+
+#[test]
+fn named_function_expressions_are_named_after_their_binding() {
+  // The class is hashed from the component name, so it has to match the one of the arrow function
+  let expected = transform_card("(props, ref) => <div ref={ref} className=\"card\" />");
+  let expected_class = &expected.components[0].class_names["card"];
+
+  // Both `Card` and `CardInner` start at the function, the outer binding is the component name
+  for _ in 0..8 {
+    let output = transform_card(
+      "function CardInner(props, ref) { return <div ref={ref} className=\"card\" />; }",
+    );
+    let [component] = output.components.as_slice() else {
+      panic!("expected one component, got {:?}", output.components);
+    };
+    assert_eq!(component.name.as_deref(), Some("Card"));
+    assert_eq!(&component.class_names["card"], expected_class);
+  }
+}
@@ -0,0 +1,31 @@
+use flair_core::patch::Patches;
+use oxc::span::Span;
+
+#[test]
+fn extend_keeps_insertions_at_the_same_offset() {
+  let source = "<div>Card</div>";
+
+  let mut patches = Patches::default();
+  patches.insert(4, " className=\"a\"".to_string());
+  let mut other = Patches::default();
+  other.insert(4, " style={{}}".to_string());
+  other.replace(Span::new(5, 9), "Box".to_string());
+  patches.extend(other);
+
+  let patched = patches.apply(source, "card.tsx");
+  assert_eq!(patched.code, "<div className=\"a\" style={{}}>Box</div>");
+}
+
+#[test]
+fn extend_overrides_replacements_of_the_same_span() {
+  let source = "<div>Card</div>";
+
+  let mut patches = Patches::default();
+  patches.replace(Span::new(5, 9), "Box".to_string());
+  let mut other = Patches::default();
+  other.replace(Span::new(5, 9), "Panel".to_string());
+  patches.extend(other);
+
+  let patched = patches.apply(source, "card.tsx");
+  assert_eq!(patched.code, "<div>Panel</div>");
+}
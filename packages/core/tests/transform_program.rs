@@ -0,0 +1,80 @@
+use flair_core::transform::{transform_program, TransformOptions};
+use oxc::allocator::Allocator;
+use oxc::ast::ast::SourceType;
+use oxc::codegen::Codegen;
+use oxc::parser::Parser;
+use oxc::semantic::SemanticBuilder;
+
+const SOURCE: &str = r#"import { Style } from "@flairjs/client";
+import { fmt } from "./fmt";
+export function Card({ tone }) {
+  return <div className="card">
+    <Style>{`.card { color: ${fmt(tone)}; }`}</Style>
+  </div>;
+}
+export const Button = () => <button className="button">Click</button>;
+Button.flair = `.button { padding: ${(props) => props.size}px; }`;
+"#;
+
+#[test]
+fn transforms_a_parsed_program_in_place() {
+  let allocator = Allocator::default();
+  let source_type = SourceType::from_path("card.tsx").unwrap();
+  let mut program = Parser::new(&allocator, SOURCE, source_type).parse().program;
+  let scoping = SemanticBuilder::new()
+    .build(&program)
+    .semantic
+    .into_scoping();
+
+  let options = TransformOptions {
+    css_output: Some("virtual".to_string()),
+    ..Default::default()
+  };
+  let output = transform_program(
+    &allocator,
+    &mut program,
+    &scoping,
+    "card.tsx",
+    options,
+    None,
+  );
+
+  assert!(output.transformed);
+  assert!(output.logs.is_empty(), "{:?}", output.logs);
+  assert_eq!(output.components.len(), 2);
+  let virtual_module_id = output.virtual_module_id.expect("virtual module id");
+
+  let code = Codegen::new().build(&program).code;
+  assert!(code.contains(&format!("import \"{}\"", virtual_module_id)));
+  assert!(!code.contains("<Style>"));
+  assert!(!code.contains("Button.flair"));
+  // The generated style bindings have no semantic information, and must not be resolved
+  assert!(code.contains(": fmt(tone)"));
+  assert!(code.contains("(__flair_props__) =>"));
+  assert!(output.css.contains("var(--flair-"));
+}
+
+#[test]
+fn leaves_programs_without_styles_untouched() {
+  let allocator = Allocator::default();
+  let source = "export const a = 1;\n";
+  let mut program = Parser::new(&allocator, source, SourceType::mjs())
+    .parse()
+    .program;
+  let scoping = SemanticBuilder::new()
+    .build(&program)
+    .semantic
+    .into_scoping();
+
+  let output = transform_program(
+    &allocator,
+    &mut program,
+    &scoping,
+    "a.js",
+    TransformOptions::default(),
+    None,
+  );
+
+  assert!(!output.transformed);
+  assert_eq!(Codegen::new().build(&program).code, source);
+}
@@ -8,5 +8,5 @@ export {
 } from "./plugin-core.js";
 
 export { shouldProcessFile } from "./file-matcher.js";
-export { transformCode } from "./transform.js";
+export { transformCode, transformCodeAsync } from "./transform.js";
 export { getUserTheme } from "./user-theme.js";
@@ -1,5 +1,7 @@
 import {
+  LogEntry,
   transformCode as rustTransformCode,
+  transformCodeAsync as rustTransformCodeAsync,
   TransformOptions,
   TransformOutput,
 } from "@flairjs/core";
@@ -37,6 +39,20 @@ const logger = {
   },
 };
 
+/**
+ * Formats a log entry as `file:line:column message [code]`, followed by the help text and code frame.
+ */
+const formatLog = (log: LogEntry) => {
+  const location = log.file
+    ? `${log.file}${log.line ? `:${log.line}:${log.column ?? 1}` : ""} `
+    : "";
+  const code = log.code ? ` [${log.code}]` : "";
+
+  return [`${location}${log.message}${code}`, log.help && `Help: ${log.help}`, log.frame]
+    .filter(Boolean)
+    .join("\n");
+};
+
 export const transformCode = (
   code: string,
   filePath: string,
@@ -44,25 +60,47 @@ export const transformCode = (
     cssPreprocessor?: (css: string) => string;
   }
 ): TransformOutput | null => {
+  const { cssPreprocessor, ...transformOptions } = options;
   const result = rustTransformCode(
     code,
     filePath,
-    {
-      cssOutDir: options.cssOutDir,
-      classNameList: options.classNameList,
-      useTheme: options.useTheme,
-      theme: options.theme,
-      appendTimestampToCssFile: options.appendTimestampToCssFile,
-    },
-    options.cssPreprocessor
+    transformOptions,
+    cssPreprocessor
   );
 
+  printLogs(result);
+  return result;
+};
+
+/**
+ * Same as `transformCode`, but runs on the libuv thread pool so the JS thread is free.
+ * `cssPreprocessor` is still called on the JS thread and must return synchronously.
+ */
+export const transformCodeAsync = async (
+  code: string,
+  filePath: string,
+  options: TransformOptions & {
+    cssPreprocessor?: (css: string) => string;
+  }
+): Promise<TransformOutput | null> => {
+  const { cssPreprocessor, ...transformOptions } = options;
+  const result = await rustTransformCodeAsync(
+    code,
+    filePath,
+    transformOptions,
+    cssPreprocessor
+  );
+
+  printLogs(result);
+  return result;
+};
+
+const printLogs = (result: TransformOutput | null) => {
   const logs = result?.logs ?? [];
 
   logs.forEach((log) => {
     if (logger[log.level]) {
-      logger[log.level](log.message);
+      logger[log.level](formatLog(log));
     }
   });
-  return result;
 };
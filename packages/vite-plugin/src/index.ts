@@ -3,7 +3,7 @@ import {
   initializeSharedContext,
   SharedPluginOptions,
   shouldProcessFile,
-  transformCode,
+  transformCodeAsync,
 } from "@flairjs/bundler-shared";
 import type { Plugin } from "vite";
 
@@ -44,7 +44,7 @@ export default async function flairJsVitePlugin(
       }
       return "";
     },
-    transform(code, id) {
+    async transform(code, id) {
       if (!shouldProcessFile(id, options?.include, options?.exclude)) {
         return null;
       }
@@ -53,7 +53,7 @@ export default async function flairJsVitePlugin(
         return null;
       }
 
-      const result = transformCode(code, id, {
+      const result = await transformCodeAsync(code, id, {
         appendTimestampToCssFile: true,
         classNameList: options?.classNameList,
         cssPreprocessor: options?.cssPreprocessor